@@ -0,0 +1,382 @@
+use crate::find_subsequence as find;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+
+// How far into a page <meta charset> is looked for, HTML section 13.2.3.2
+const PRESCAN_BYTES: usize = 1024;
+
+// Source
+// Where the encoding of a page came from, in the order they are tried
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    // A byte order mark, nothing overrides it
+    Bom,
+    // Picked by hand with the encoding command
+    Forced,
+    // The charset parameter of Content-Type
+    ContentType,
+    // <meta charset> or <meta http-equiv="Content-Type">
+    Meta,
+    // Guessed from the bytes themselves
+    Detected,
+}
+
+impl Source {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Source::Bom => "the byte order mark",
+            Source::Forced => "the encoding command",
+            Source::ContentType => "the Content-Type header",
+            Source::Meta => "a <meta> tag",
+            Source::Detected => "the content",
+        }
+    }
+}
+
+// decode
+// Decode an HTML page the way the WHATWG encoding sniffing algorithm does, HTML section 13.2.3
+// ---- a BOM comes first, then "forced", the Content-Type "charset", the <meta> prescan, and a
+//      guess made from the bytes and the "tld" of the host they came from
+pub fn decode(
+    body: &[u8],
+    charset: Option<&str>,
+    forced: Option<&'static Encoding>,
+    tld: Option<&str>,
+) -> (String, &'static Encoding, Source) {
+    let (encoding, source) = if let Some((encoding, _)) = Encoding::for_bom(body) {
+        (encoding, Source::Bom)
+    } else if let Some(encoding) = forced {
+        (encoding, Source::Forced)
+    } else if let Some(encoding) = charset.and_then(|label| Encoding::for_label(label.as_bytes())) {
+        (encoding, Source::ContentType)
+    } else if let Some(encoding) = prescan(body) {
+        (encoding, Source::Meta)
+    } else {
+        (detect(body, tld), Source::Detected)
+    };
+    // Malformed sequences turn into U+FFFD, the BOM is dropped
+    let (text, encoding, _) = encoding.decode(body);
+    (text.into_owned(), encoding, source)
+}
+
+// decode_text
+// Decode text that isn't HTML, only a BOM, the Content-Type "charset" or a guess can tell
+pub fn decode_text(body: &[u8], charset: Option<&str>) -> String {
+    let encoding = charset
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or_else(|| detect(body, None));
+    encoding.decode(body).0.into_owned()
+}
+
+// The encoding an encoding command's label names, None for labels the spec doesn't know
+pub fn for_label(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+}
+
+// detect
+// Guess the encoding of bytes that don't say, whatever legacy encoding fits them and the "tld"
+// best
+// ---- browsers never guess UTF-8 so pages don't come to rely on it, this one does since most
+//      undeclared pages are UTF-8 by now
+fn detect(body: &[u8], tld: Option<&str>) -> &'static Encoding {
+    let mut detector = EncodingDetector::new();
+    detector.feed(body, true);
+    let tld = tld.map(str::to_ascii_lowercase).filter(|tld| {
+        tld.bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+    });
+    detector.guess(tld.as_deref().map(str::as_bytes), true)
+}
+
+// prescan
+// Look for the encoding a page declares in <meta> within its first bytes, HTML section
+// 13.2.3.2 "prescan a byte stream to determine its encoding"
+pub fn prescan(body: &[u8]) -> Option<&'static Encoding> {
+    let input = &body[..body.len().min(PRESCAN_BYTES)];
+    let mut position = 0;
+    while position < input.len() {
+        let rest = &input[position..];
+        if rest.starts_with(b"<!--") {
+            // The "-->" may share its dashes with the "<!--"
+            position += 2 + find(&rest[2..], b"-->").map_or(rest.len(), |end| end + 3);
+        } else if starts_with_ignore_case(rest, b"<meta")
+            && rest
+                .get(5)
+                .is_some_and(|&byte| is_space(byte) || byte == b'/')
+        {
+            position += 6;
+            if let Some(encoding) = meta(input, &mut position) {
+                return Some(encoding);
+            }
+        } else if rest.len() > 2
+            && rest[0] == b'<'
+            && (rest[1].is_ascii_alphabetic() || (rest[1] == b'/' && rest[2].is_ascii_alphabetic()))
+        {
+            // Any other tag, its attributes are read only to get past them
+            position += 1 + rest[1..]
+                .iter()
+                .position(|&byte| is_space(byte) || byte == b'>')
+                .unwrap_or(rest.len() - 1);
+            while attribute(input, &mut position).is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            position += find(rest, b">").map_or(rest.len(), |end| end + 1);
+        } else {
+            position += 1;
+        }
+    }
+    None
+}
+
+// The attributes of a <meta> the prescan is at, and the encoding they declare
+fn meta(input: &[u8], position: &mut usize) -> Option<&'static Encoding> {
+    let (mut seen, mut got_pragma, mut need_pragma, mut charset) = (vec![], false, None, None);
+    while let Some((name, value)) = attribute(input, position) {
+        if seen.contains(&name) {
+            continue;
+        }
+        match name.as_str() {
+            "http-equiv" if value == "content-type" => got_pragma = true,
+            "content" if charset.is_none() => {
+                if let Some(encoding) = charset_from_content(&value) {
+                    charset = Some(encoding);
+                    need_pragma = Some(true);
+                }
+            }
+            "charset" if charset.is_none() => {
+                charset = Encoding::for_label(value.as_bytes());
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        seen.push(name);
+    }
+    if need_pragma? && !got_pragma {
+        return None;
+    }
+    // A page that reached the prescan can't be UTF-16 anymore, it would have had a BOM
+    Some(match charset? {
+        encoding if encoding == UTF_16BE || encoding == UTF_16LE => UTF_8,
+        encoding if encoding == X_USER_DEFINED => WINDOWS_1252,
+        encoding => encoding,
+    })
+}
+
+// attribute
+// The next attribute of a tag as a lowercased name and value, None once the tag ends, HTML
+// section 13.2.3.2 "get an attribute"
+fn attribute(input: &[u8], position: &mut usize) -> Option<(String, String)> {
+    let next = |position: &usize| input.get(*position).copied();
+    while next(position).is_some_and(|byte| is_space(byte) || byte == b'/') {
+        *position += 1;
+    }
+    if next(position)? == b'>' {
+        return None;
+    }
+
+    let mut name = vec![];
+    loop {
+        match next(position)? {
+            b'=' if !name.is_empty() => break,
+            byte if is_space(byte) => {
+                while next(position).is_some_and(is_space) {
+                    *position += 1;
+                }
+                if next(position)? != b'=' {
+                    return Some((lowercase(&name), String::new()));
+                }
+                break;
+            }
+            b'/' | b'>' => return Some((lowercase(&name), String::new())),
+            byte => name.push(byte),
+        }
+        *position += 1;
+    }
+
+    // Past the "="
+    *position += 1;
+    while next(position).is_some_and(is_space) {
+        *position += 1;
+    }
+    let mut value = vec![];
+    match next(position)? {
+        quote @ (b'"' | b'\'') => {
+            *position += 1;
+            loop {
+                let byte = next(position)?;
+                *position += 1;
+                if byte == quote {
+                    break;
+                }
+                value.push(byte);
+            }
+        }
+        b'>' => {}
+        _ => {
+            while let Some(byte) = next(position).filter(|&byte| !is_space(byte) && byte != b'>') {
+                value.push(byte);
+                *position += 1;
+            }
+        }
+    }
+    Some((lowercase(&name), lowercase(&value)))
+}
+
+// charset_from_content
+// The charset a <meta http-equiv> content value names, HTML section 2.5.6 "algorithm for
+// extracting a character encoding from a meta element"
+fn charset_from_content(content: &str) -> Option<&'static Encoding> {
+    let mut rest = content;
+    loop {
+        let start = rest.find("charset")?;
+        rest = rest[start + "charset".len()..].trim_start_matches(is_space_char);
+        if let Some(after) = rest.strip_prefix('=') {
+            rest = after.trim_start_matches(is_space_char);
+            break;
+        }
+    }
+    let label = match rest.chars().next()? {
+        quote @ ('"' | '\'') => rest[1..].split_once(quote)?.0,
+        _ => rest.split(|c: char| is_space_char(c) || c == ';').next()?,
+    };
+    Encoding::for_label(label.as_bytes())
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn is_space(byte: u8) -> bool {
+    matches!(byte, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+fn is_space_char(c: char) -> bool {
+    c.is_ascii() && is_space(c as u8)
+}
+
+fn lowercase(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{ISO_8859_2, KOI8_R, SHIFT_JIS, WINDOWS_1251};
+
+    #[test]
+    fn prescan_finds_meta_charset() {
+        assert_eq!(
+            prescan(b"<!DOCTYPE html><html><head><META Charset='ISO-8859-2'>"),
+            Some(ISO_8859_2)
+        );
+        assert_eq!(prescan(b"<meta charset=koi8-r>"), Some(KOI8_R));
+        assert_eq!(
+            prescan(b"<meta/charset=\"windows-1251\"/>"),
+            Some(WINDOWS_1251)
+        );
+        assert_eq!(prescan(b"<html><body>no declaration</body></html>"), None);
+    }
+
+    #[test]
+    fn prescan_needs_http_equiv_for_content() {
+        assert_eq!(
+            prescan(b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=shift_jis\">"),
+            Some(SHIFT_JIS)
+        );
+        assert_eq!(
+            prescan(b"<meta content='text/html;charset = \"koi8-r\"' http-equiv=content-type>"),
+            Some(KOI8_R)
+        );
+        assert_eq!(
+            prescan(b"<meta content=\"text/html; charset=shift_jis\">"),
+            None
+        );
+    }
+
+    #[test]
+    fn prescan_skips_comments_and_other_tags() {
+        assert_eq!(
+            prescan(b"<!-- <meta charset=koi8-r> --><meta charset=utf-8>"),
+            Some(UTF_8)
+        );
+        assert_eq!(
+            prescan(b"<div title=\"<meta charset=koi8-r>\"><meta charset=windows-1251>"),
+            Some(WINDOWS_1251)
+        );
+        assert_eq!(prescan(b"<metadata charset=koi8-r>"), None);
+        // The first of a repeated attribute counts, a label nobody knows is passed over
+        assert_eq!(
+            prescan(b"<meta charset=koi8-r charset=utf-8>"),
+            Some(KOI8_R)
+        );
+        assert_eq!(
+            prescan(b"<meta charset=bogus><meta charset=koi8-r>"),
+            Some(KOI8_R)
+        );
+    }
+
+    #[test]
+    fn prescan_stops_after_1024_bytes() {
+        let mut page = vec![b' '; PRESCAN_BYTES];
+        page.extend_from_slice(b"<meta charset=koi8-r>");
+        assert_eq!(prescan(&page), None);
+        // Cut off inside the tag
+        page.drain(..10);
+        assert_eq!(prescan(&page), None);
+        page.drain(..20);
+        assert_eq!(prescan(&page), Some(KOI8_R));
+    }
+
+    #[test]
+    fn prescan_never_picks_utf16() {
+        assert_eq!(prescan(b"<meta charset=utf-16le>"), Some(UTF_8));
+        assert_eq!(
+            prescan(b"<meta charset=x-user-defined>"),
+            Some(WINDOWS_1252)
+        );
+    }
+
+    #[test]
+    fn decode_tries_each_source_in_turn() {
+        let page = b"<meta charset=koi8-r>\xf0\xd2\xc9\xd7\xc5\xd4";
+        let (text, encoding, source) = decode(page, None, None, None);
+        assert_eq!((encoding, source), (KOI8_R, Source::Meta));
+        assert!(text.ends_with("\u{41f}\u{440}\u{438}\u{432}\u{435}\u{442}"));
+
+        let (_, encoding, source) = decode(page, Some("windows-1251"), None, None);
+        assert_eq!((encoding, source), (WINDOWS_1251, Source::ContentType));
+        let (_, encoding, source) = decode(page, Some("no-such-charset"), None, None);
+        assert_eq!((encoding, source), (KOI8_R, Source::Meta));
+        let (_, encoding, source) = decode(page, Some("windows-1251"), Some(ISO_8859_2), None);
+        assert_eq!((encoding, source), (ISO_8859_2, Source::Forced));
+
+        let bom = [b"\xef\xbb\xbf".as_slice(), page].concat();
+        let (text, encoding, source) = decode(&bom, Some("windows-1251"), Some(KOI8_R), None);
+        assert_eq!((encoding, source), (UTF_8, Source::Bom));
+        assert!(text.starts_with("<meta"));
+        let (text, encoding, source) = decode(b"\xff\xfeh\0i\0", None, None, None);
+        assert_eq!(
+            (text.as_str(), encoding, source),
+            ("hi", UTF_16LE, Source::Bom)
+        );
+    }
+
+    #[test]
+    fn decode_guesses_without_a_declaration() {
+        let (text, encoding, source) = decode("caf\u{e9}".as_bytes(), None, None, None);
+        assert_eq!(
+            (text.as_str(), encoding, source),
+            ("caf\u{e9}", UTF_8, Source::Detected)
+        );
+        let (text, encoding, _) = decode(b"caf\xe9", None, None, None);
+        assert_eq!((text.as_str(), encoding), ("caf\u{e9}", WINDOWS_1252));
+        assert_eq!(decode_text(b"caf\xe9", Some("iso-8859-2")), "caf\u{e9}");
+        assert_eq!(decode_text(b"\xa3", Some("iso-8859-2")), "\u{141}");
+    }
+
+    #[test]
+    fn reads_labels() {
+        assert_eq!(for_label(" Latin1 "), Some(WINDOWS_1252));
+        assert_eq!(for_label("no-such-charset"), None);
+        assert_eq!(Source::Meta.describe(), "a <meta> tag");
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{http::Request, url::Url};
+use std::fs;
+
+// Methods the request command sends
+const REQUEST_METHODS: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+
+// build_request
+// Build the request of "request METHOD URL [-H "Name: value"]... [-d @FILE | -d TEXT]", a body
+// without a Content-Type is sent as a form like curl does
+pub fn build_request(args: &str) -> Result<Request, String> {
+    let mut args = split_args(args).into_iter();
+    let method = args.next().unwrap_or_default().to_ascii_uppercase();
+    if !REQUEST_METHODS.contains(&method.as_str()) {
+        return Err(format!(
+            "Method '{method}' isn't one of {}",
+            REQUEST_METHODS.join(", ")
+        ));
+    }
+    let (mut url, mut headers, mut body) = (None, vec![], None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-H" => {
+                let header = args.next().ok_or("-H needs a \"Name: value\" header")?;
+                match header.split_once(':') {
+                    Some((name, value))
+                        if !name.trim().is_empty() && !name.contains(char::is_whitespace) =>
+                    {
+                        headers.push((name.to_string(), value.trim().to_string()));
+                    }
+                    _ => return Err(format!("'{header}' isn't a \"Name: value\" header")),
+                }
+            }
+            "-d" => {
+                let data = args.next().ok_or("-d needs @FILE or the body itself")?;
+                body = Some(match data.strip_prefix('@') {
+                    Some(file) => {
+                        fs::read(file).map_err(|err| format!("Couldn't read {file}, {err}"))?
+                    }
+                    None => data.into_bytes(),
+                });
+            }
+            option if option.starts_with('-') => return Err(format!("Unknown option '{option}'")),
+            _ if url.is_some() => return Err(format!("'{arg}' comes after the URL")),
+            _ => url = Some(Url::parse(&arg).map_err(|err| format!("Invalid URL, {err}"))?),
+        }
+    }
+
+    let mut request = Request::new(&method, url.ok_or("request needs a URL")?);
+    for (name, value) in &headers {
+        request = request.header(name, value);
+    }
+    if let Some(body) = body {
+        if !request.has_header("Content-Type") {
+            request = request.header("Content-Type", "application/x-www-form-urlencoded");
+        }
+        request = request.body(body);
+    }
+    Ok(request)
+}
+
+// split_args
+// Split a command's arguments on whitespace, double quotes keep a value with spaces in one piece
+// and a backslash takes the next character as it is
+pub fn split_args(args: &str) -> Vec<String> {
+    let mut split = vec![];
+    let (mut current, mut quoted, mut started) = (String::new(), false, false);
+    let mut chars = args.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => (quoted, started) = (!quoted, true),
+            '\\' => {
+                current.extend(chars.next());
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    split.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        split.push(current);
+    }
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_whitespace_outside_quotes() {
+        assert_eq!(
+            split_args(r#"  one "two three"  fo"u"r \"five\" "" back\\slash"#),
+            ["one", "two three", "four", "\"five\"", "", "back\\slash"]
+        );
+        assert!(split_args("   ").is_empty());
+    }
+
+    #[test]
+    fn builds_the_request_command() {
+        let request = build_request(
+            r#"post http://example.com/api -H "Accept: text/plain" -H X-Empty: -d "a=1 b""#,
+        )
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.url.to_string(), "http://example.com/api");
+        assert_eq!(request.headers.get("Accept"), Some("text/plain"));
+        assert_eq!(request.headers.get("X-Empty"), Some(""));
+        assert_eq!(
+            request.headers.get("Content-Type"),
+            Some("application/x-www-form-urlencoded")
+        );
+        assert_eq!(request.body, b"a=1 b");
+
+        let request =
+            build_request(r#"PUT http://example.com/ -d {} -H "Content-Type: application/json""#)
+                .unwrap();
+        assert_eq!(
+            request.headers.get("Content-Type"),
+            Some("application/json")
+        );
+
+        let request = build_request("DELETE http://example.com/item").unwrap();
+        assert_eq!(request.headers.get("Content-Type"), None);
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn reads_the_body_from_a_file() {
+        let path = std::env::temp_dir().join(format!("request-body-{}", std::process::id()));
+        fs::write(&path, b"from a file").unwrap();
+        let request =
+            build_request(&format!("POST http://example.com/ -d @{}", path.display())).unwrap();
+        assert_eq!(request.body, b"from a file");
+        fs::remove_file(&path).unwrap();
+        assert!(
+            build_request(&format!("POST http://example.com/ -d @{}", path.display()))
+                .unwrap_err()
+                .starts_with("Couldn't read")
+        );
+    }
+
+    #[test]
+    fn refuses_malformed_commands() {
+        for args in [
+            "FETCH http://example.com/",
+            "GET",
+            "GET http://example.com/ -H",
+            "GET http://example.com/ -H NoColon",
+            "GET http://example.com/ -H \"Bad Name: x\"",
+            "GET http://example.com/ -d",
+            "GET http://example.com/ -X",
+            "GET http://example.com/ http://example.org/",
+        ] {
+            assert!(build_request(args).is_err(), "{args}");
+        }
+    }
+}
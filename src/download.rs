@@ -0,0 +1,483 @@
+use crate::{
+    http::{Body, Client, Error, Request, Response},
+    url::Url,
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, prelude::*, SeekFrom},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+// How often the progress callback fires at most, the last update always goes through
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// Segments smaller than this aren't worth the extra connection
+const MIN_SEGMENT_BYTES: u64 = 1024 * 1024;
+
+pub const DEFAULT_SEGMENTS: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    pub downloaded: u64,
+    // From Content-Length, None when the server didn't say
+    pub total: Option<u64>,
+    // Bytes an earlier attempt already left on disk, counted in "downloaded" but not in the rate
+    pub resumed_from: u64,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> Option<f64> {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| (self.downloaded as f64 / total as f64).min(1.0))
+    }
+
+    // Average bytes per second since the download started
+    pub fn rate(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.downloaded.saturating_sub(self.resumed_from) as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.total?.saturating_sub(self.downloaded);
+        let rate = self.rate();
+        (rate > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / rate))
+    }
+}
+
+// stream_to
+// Copy "body" into "file" as it arrives, "on_progress" gets called along the way
+// ---- "resumed_from" is the size of the file before this body, zero for a fresh download
+pub fn stream_to<R: Read, W: Write>(
+    body: &mut R,
+    file: &mut W,
+    total: Option<u64>,
+    resumed_from: u64,
+    mut on_progress: impl FnMut(&Progress),
+) -> Result<u64, Error> {
+    let started = Instant::now();
+    let mut last_update = started;
+    let mut buf = vec![0; 64 * 1024];
+    let mut progress = Progress {
+        downloaded: resumed_from,
+        total,
+        resumed_from,
+        elapsed: Duration::ZERO,
+    };
+    on_progress(&progress);
+
+    loop {
+        let read = match body.read(&mut buf) {
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buf[..read])?;
+        progress.downloaded += read as u64;
+
+        if last_update.elapsed() >= PROGRESS_INTERVAL {
+            last_update = Instant::now();
+            progress.elapsed = started.elapsed();
+            on_progress(&progress);
+        }
+    }
+    file.flush()?;
+
+    progress.elapsed = started.elapsed();
+    on_progress(&progress);
+    Ok(progress.downloaded)
+}
+
+// validator
+// Strong ETag or else Last-Modified of a response, for If-Range to ask for the same version
+pub fn validator(response: &Response) -> Option<&str> {
+    response
+        .header("ETag")
+        .filter(|etag| !etag.starts_with("W/"))
+        .or(response.header("Last-Modified"))
+}
+
+// content_range
+// First byte and total size from a 206's Content-Range, e.g. "bytes 100-199/1000"
+// ---- the total is None when the server sent "*"
+pub fn content_range(response: &Response) -> Option<(u64, Option<u64>)> {
+    let range = response.header("Content-Range")?.strip_prefix("bytes ")?;
+    let (span, total) = range.split_once('/')?;
+    let (first, _) = span.split_once('-')?;
+    Some((first.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+// range_total
+// Size of the file when the response allows it to be fetched in byte ranges
+pub fn range_total(request: &Request, response: &Response) -> Option<u64> {
+    let accepts_ranges = response.header("Accept-Ranges").is_some_and(|units| {
+        units
+            .split(',')
+            .any(|unit| unit.trim().eq_ignore_ascii_case("bytes"))
+    });
+    // Ranges address the encoded bytes, a decoded body can't be pieced together from them
+    let decoded = request.decode_content
+        && response
+            .header("Content-Encoding")
+            .is_some_and(|coding| !coding.eq_ignore_ascii_case("identity"));
+    if response.status != 200
+        || !accepts_ranges
+        || decoded
+        || response.header("Transfer-Encoding").is_some()
+    {
+        return None;
+    }
+    response.header("Content-Length")?.parse().ok()
+}
+
+// segment_count
+// Split "total" bytes into at most "requested" segments that are each worth a connection
+pub fn segment_count(total: u64, requested: usize) -> usize {
+    (total / MIN_SEGMENT_BYTES).clamp(1, requested.max(1) as u64) as usize
+}
+
+// stream_segmented
+// Fetch the file behind "response" in "segments" byte ranges on worker threads, each one
+// writing its own part of "path", which is preallocated to the full size
+// ---- "body" is closed right away and every segment gets a Range request of its own, reading
+//      the first one off "body" would leave the rest of it on the wire for nothing
+// ---- If-Range makes sure every segment comes from the same version of the file
+// ---- "segments" is clamped with segment_count, an empty file isn't fetched again at all
+// ---- falls back to streaming a 200 whole when a range isn't honoured, returns the number of
+//      connections the file came down over
+pub fn stream_segmented(
+    client: &Client,
+    request: &Request,
+    response: &Response,
+    body: Body,
+    path: &Path,
+    segments: usize,
+    mut on_progress: impl FnMut(&Progress),
+) -> Result<usize, Error> {
+    drop(body);
+    let total = range_total(request, response).ok_or(Error::RangeIgnored)?;
+    if total == 0 {
+        File::create(path)?;
+        return Ok(1);
+    }
+    let segments = segment_count(total, segments);
+    let segment_len = total.div_ceil(segments as u64);
+    let validator = validator(response);
+
+    // Every range is asked for up front so a server that ignores them is caught before any
+    // worker starts writing
+    let mut bodies = vec![];
+    for index in 0..segments as u64 {
+        let start = index * segment_len;
+        let end = (start + segment_len).min(total) - 1;
+        let mut range_request = request
+            .clone()
+            .header("Range", &format!("bytes={start}-{end}"));
+        if let Some(validator) = validator {
+            range_request = range_request.header("If-Range", validator);
+        }
+        range_request.decode_content = false;
+
+        let (range_response, mut range_body) = client.clone().send_streaming(&range_request)?;
+        let expected_range = format!("bytes {start}-{end}/{total}");
+        if range_response.status == 200 {
+            // The whole file instead, Range was ignored or If-Range found it changed
+            drop(bodies);
+            let mut file = io::BufWriter::new(File::create(path)?);
+            let total = range_response.content_length()?;
+            stream_to(&mut range_body, &mut file, total, 0, on_progress)?;
+            file.flush()?;
+            return Ok(1);
+        }
+        if range_response.status != 206
+            || range_response.header("Content-Range") != Some(expected_range.as_str())
+        {
+            return Err(Error::RangeIgnored);
+        }
+        bodies.push(range_body);
+    }
+
+    File::create(path)?.set_len(total)?;
+
+    let started = Instant::now();
+    let mut progress = Progress {
+        downloaded: 0,
+        total: Some(total),
+        resumed_from: 0,
+        elapsed: Duration::ZERO,
+    };
+    on_progress(&progress);
+
+    let (tx, rx) = mpsc::channel::<u64>();
+    let results = thread::scope(|scope| {
+        let mut workers = vec![];
+        for (index, mut segment_body) in bodies.into_iter().enumerate() {
+            let start = index as u64 * segment_len;
+            let end = (start + segment_len).min(total) - 1;
+            let tx = tx.clone();
+            workers.push(scope.spawn(move || -> Result<(), Error> {
+                let mut file = OpenOptions::new().write(true).open(path)?;
+                file.seek(SeekFrom::Start(start))?;
+                let mut remaining = end + 1 - start;
+                let mut buf = vec![0; 64 * 1024];
+                while remaining > 0 {
+                    let max = buf.len().min(remaining as usize);
+                    let read = match segment_body.read(&mut buf[..max]) {
+                        Ok(0) => return Err(Error::MalformedResponse("range ended early")),
+                        Ok(read) => read,
+                        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(err) => return Err(err.into()),
+                    };
+                    file.write_all(&buf[..read])?;
+                    remaining -= read as u64;
+                    // The receiving end only goes away once every worker is done
+                    let _ = tx.send(read as u64);
+                }
+                file.flush()?;
+                Ok(())
+            }));
+        }
+        drop(tx);
+
+        // Progress is reported from this thread only, the callback may draw on screen
+        let mut last_update = started;
+        loop {
+            match rx.recv_timeout(PROGRESS_INTERVAL) {
+                Ok(read) => progress.downloaded += read,
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            if last_update.elapsed() >= PROGRESS_INTERVAL {
+                last_update = Instant::now();
+                progress.elapsed = started.elapsed();
+                on_progress(&progress);
+            }
+        }
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    results.into_iter().collect::<Result<(), Error>>()?;
+
+    // What the segments delivered first, then what ended up on disk
+    let size = fs::metadata(path)?.len();
+    let actual = if progress.downloaded != total {
+        progress.downloaded
+    } else {
+        size
+    };
+    if actual != total {
+        return Err(Error::SizeMismatch {
+            expected: total,
+            actual,
+        });
+    }
+
+    progress.elapsed = started.elapsed();
+    on_progress(&progress);
+    Ok(segments)
+}
+
+// Partial
+// A download still in progress or cut short, its bytes go to "<name>.part" and the URL it comes
+// from plus the validator to resume it with to "<name>.part.info" next to it
+// ---- without a validator the download starts over, e.g. for decoded or segmented bodies whose
+//      bytes on disk don't line up with the ones on the wire
+#[derive(Clone, Debug)]
+pub struct Partial {
+    // Where the file ends up once complete
+    pub path: PathBuf,
+    pub url: Url,
+    pub validator: Option<String>,
+}
+
+impl Partial {
+    pub fn new(path: PathBuf, url: Url) -> Partial {
+        Partial {
+            path,
+            url: Url {
+                fragment: None,
+                ..url
+            },
+            validator: None,
+        }
+    }
+
+    // find
+    // The partial download of "url" in "dir", if there's one, the fragment doesn't matter
+    pub fn find(dir: &Path, url: &Url) -> Option<Partial> {
+        let url = Url {
+            fragment: None,
+            ..url.clone()
+        }
+        .to_string();
+        fs::read_dir(dir).ok()?.flatten().find_map(|entry| {
+            let info_path = entry.path();
+            let filename = info_path
+                .file_name()?
+                .to_str()?
+                .strip_suffix(".part.info")?;
+            let info = fs::read_to_string(&info_path).ok()?;
+            let mut lines = info.lines();
+            if lines.next()? != url {
+                return None;
+            }
+            Some(Partial {
+                path: dir.join(filename),
+                url: Url::parse(&url).ok()?,
+                validator: lines
+                    .next()
+                    .filter(|line| !line.is_empty())
+                    .map(String::from),
+            })
+        })
+    }
+
+    pub fn part_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".part");
+        PathBuf::from(path)
+    }
+
+    fn info_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".part.info");
+        PathBuf::from(path)
+    }
+
+    // Bytes already on disk
+    pub fn downloaded(&self) -> u64 {
+        fs::metadata(self.part_path()).map_or(0, |metadata| metadata.len())
+    }
+
+    // resume_request
+    // Ask for the rest of the file only, If-Range turns it into the whole file again should it
+    // have changed since
+    // ---- the stored bytes are the ones on the wire, so they must not be decoded this time either
+    pub fn resume_request(&self, mut request: Request) -> Request {
+        let downloaded = self.downloaded();
+        if let Some(validator) = self.validator.as_deref().filter(|_| downloaded > 0) {
+            request = request
+                .header("Range", &format!("bytes={downloaded}-"))
+                .header("If-Range", validator);
+            request.decode_content = false;
+        }
+        request
+    }
+
+    // save_info
+    // Record the URL and validator so a later download of the same URL can pick up from here
+    pub fn save_info(&self) -> io::Result<()> {
+        fs::write(
+            self.info_path(),
+            format!(
+                "{}\n{}\n",
+                self.url,
+                self.validator.as_deref().unwrap_or_default()
+            ),
+        )
+    }
+
+    // finish
+    // Move the complete file to its final name
+    pub fn finish(&self) -> io::Result<()> {
+        fs::rename(self.part_path(), &self.path)?;
+        fs::remove_file(self.info_path())
+    }
+
+    pub fn remove(&self) {
+        let _ = fs::remove_file(self.part_path());
+        let _ = fs::remove_file(self.info_path());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(head: &str) -> Response {
+        Response::read_head(&mut head.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn reads_content_range() {
+        let partial = |range: &str| {
+            response(&format!(
+                "HTTP/1.1 206 Partial Content\r\nContent-Range: {range}\r\n\r\n"
+            ))
+        };
+        assert_eq!(
+            content_range(&partial("bytes 100-199/1000")),
+            Some((100, Some(1000)))
+        );
+        assert_eq!(content_range(&partial("bytes 0-9/*")), Some((0, None)));
+        assert_eq!(content_range(&partial("items 0-9/10")), None);
+        assert_eq!(content_range(&partial("bytes */1000")), None);
+    }
+
+    #[test]
+    fn prefers_a_strong_etag_as_validator() {
+        let head = "HTTP/1.1 200 OK\r\nETag: \"abc\"\r\nLast-Modified: Tue, 01 Jan 2030 00:00:00 GMT\r\n\r\n";
+        assert_eq!(validator(&response(head)), Some("\"abc\""));
+        let head = "HTTP/1.1 200 OK\r\nETag: W/\"abc\"\r\nLast-Modified: Tue, 01 Jan 2030 00:00:00 GMT\r\n\r\n";
+        assert_eq!(
+            validator(&response(head)),
+            Some("Tue, 01 Jan 2030 00:00:00 GMT")
+        );
+    }
+
+    #[test]
+    fn splits_into_worthwhile_segments() {
+        assert_eq!(segment_count(100, 4), 1);
+        assert_eq!(segment_count(3 * MIN_SEGMENT_BYTES, 4), 3);
+        assert_eq!(segment_count(100 * MIN_SEGMENT_BYTES, 4), 4);
+        assert_eq!(segment_count(100 * MIN_SEGMENT_BYTES, 0), 1);
+    }
+
+    #[test]
+    fn resumes_from_the_part_file() {
+        let dir = std::env::temp_dir().join(format!("partial-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let url = Url::parse("http://example.com/file.bin").unwrap();
+        let mut partial = Partial::new(dir.join("file.bin"), url.join("#top").unwrap());
+        partial.validator = Some(String::from("\"v1\""));
+        partial.save_info().unwrap();
+        fs::write(partial.part_path(), b"12345").unwrap();
+
+        let found = Partial::find(&dir, &url.join("#part-2").unwrap()).unwrap();
+        assert_eq!(found.url, url);
+        assert_eq!(found.path, dir.join("file.bin"));
+        assert_eq!(found.validator.as_deref(), Some("\"v1\""));
+        assert_eq!(found.downloaded(), 5);
+        let request = found.resume_request(Request::get(url.clone()));
+        assert_eq!(request.headers.get("Range"), Some("bytes=5-"));
+        assert_eq!(request.headers.get("If-Range"), Some("\"v1\""));
+        assert!(!request.decode_content);
+        assert!(Partial::find(&dir, &Url::parse("http://example.com/other").unwrap()).is_none());
+
+        found.finish().unwrap();
+        assert_eq!(fs::read(dir.join("file.bin")).unwrap(), b"12345");
+        assert!(Partial::find(&dir, &url).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn starts_over_without_a_validator() {
+        let url = Url::parse("http://example.com/file.bin").unwrap();
+        let partial = Partial::new(PathBuf::from("/nonexistent/file.bin"), url.clone());
+        let request = partial.resume_request(Request::get(url));
+        assert!(!request.headers.contains("Range"));
+    }
+}
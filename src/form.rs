@@ -0,0 +1,1044 @@
+use crate::{http::Request, url::Url};
+use encoding_rs::Encoding;
+use html_parser::{Dom, Element, Node};
+use ncurses::{KEY_BACKSPACE, KEY_LEFT, KEY_RIGHT};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const URLENCODED: &str = "application/x-www-form-urlencoded";
+const MULTIPART: &str = "multipart/form-data";
+const TEXT_PLAIN: &str = "text/plain";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlKind {
+    // Every single line <input> that takes text, e.g. text, search, email, number
+    Text,
+    Password,
+    Hidden,
+    Checkbox,
+    Radio,
+    Select,
+    TextArea,
+    Submit,
+    // <input type="image">, submits the coordinates of the click
+    Image,
+    File,
+    // Buttons that never submit the form, i.e. type="button" and type="reset"
+    Button,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectOption {
+    pub value: String,
+    pub label: String,
+    pub selected: bool,
+}
+
+// Control
+// A form control with its current value, which starts out as the default of the markup
+// ---- checkboxes and radios use "checked" and submit "value" only when checked
+// ---- a select's value lives in its options, "multiple" ones may have several selected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Control {
+    // Place among every control of the page in document order, forms or not
+    pub position: usize,
+    pub kind: ControlKind,
+    pub name: String,
+    pub value: String,
+    // Text shown on buttons and next to nothing else
+    pub label: String,
+    pub checked: bool,
+    pub options: Vec<SelectOption>,
+    pub multiple: bool,
+    pub disabled: bool,
+}
+
+impl Control {
+    fn new(kind: ControlKind, element: &Element) -> Control {
+        Control {
+            position: 0,
+            kind,
+            name: attribute(element, "name").unwrap_or_default().to_string(),
+            value: attribute(element, "value").unwrap_or_default().to_string(),
+            label: String::new(),
+            checked: attribute(element, "checked").is_some(),
+            options: vec![],
+            multiple: attribute(element, "multiple").is_some(),
+            disabled: attribute(element, "disabled").is_some(),
+        }
+    }
+
+    // from_element
+    // The control an element stands for, None for anything that isn't one
+    fn from_element(element: &Element) -> Option<Control> {
+        let kind = control_kind(element)?;
+        let mut control = Control::new(kind, element);
+        match kind {
+            ControlKind::Checkbox | ControlKind::Radio if attribute(element, "value").is_none() => {
+                control.value = String::from("on");
+            }
+            ControlKind::Submit | ControlKind::Button
+                if element.name.eq_ignore_ascii_case("button") =>
+            {
+                control.label = text_content(element);
+            }
+            ControlKind::Submit if control.value.is_empty() => {
+                control.label = String::from("Submit");
+            }
+            ControlKind::Submit | ControlKind::Button => control.label = control.value.clone(),
+            ControlKind::TextArea => {
+                // The newline right after <textarea> isn't part of the value
+                let text = text_content(element);
+                control.value = text.strip_prefix('\n').unwrap_or(&text).to_string();
+            }
+            ControlKind::Select => {
+                control.options = options(element);
+                // A single select always has something selected, the first option by default
+                if !control.multiple {
+                    match control.options.iter().rposition(|option| option.selected) {
+                        Some(index) => control.select(index),
+                        None if !control.options.is_empty() => control.select(0),
+                        None => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        Some(control)
+    }
+
+    // Pick option "index" of a select, a single select lets go of the one picked before
+    pub fn select(&mut self, index: usize) {
+        for (position, option) in self.options.iter_mut().enumerate() {
+            if position == index {
+                option.selected = !self.multiple || !option.selected;
+            } else if !self.multiple {
+                option.selected = false;
+            }
+        }
+    }
+
+    // Whether the user can focus and change the control, hidden and disabled ones are left alone
+    // as are plain buttons and file pickers
+    pub fn is_editable(&self) -> bool {
+        !self.disabled
+            && !matches!(
+                self.kind,
+                ControlKind::Hidden | ControlKind::Button | ControlKind::File
+            )
+    }
+
+    // Whether the control may end up in the submitted data at all
+    pub fn is_submittable(&self) -> bool {
+        !self.disabled && !self.name.is_empty() && self.kind != ControlKind::Button
+    }
+}
+
+// Form
+// A <form> with its controls in document order, "action" is already resolved against the page
+// ---- an action that isn't an http(s) URL, e.g. javascript:, is kept as Err and can't be
+//      submitted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Form {
+    pub id: Option<String>,
+    pub action: Result<Url, String>,
+    // Upper case, GET or POST
+    pub method: String,
+    pub enctype: String,
+    pub controls: Vec<Control>,
+}
+
+impl Form {
+    fn new(element: &Element, page_url: &Url) -> Form {
+        // A missing or empty action submits to the page itself
+        let action = match attribute(element, "action").map(str::trim) {
+            Some(action) if !action.is_empty() => page_url
+                .join(action)
+                .ok()
+                .filter(|url| url.scheme == "http" || url.scheme == "https")
+                .ok_or_else(|| action.to_string()),
+            _ => Ok(page_url.clone()),
+        };
+        let method = match attribute(element, "method") {
+            Some(method) if method.eq_ignore_ascii_case("post") => "POST",
+            _ => "GET",
+        };
+        let enctype = match attribute(element, "enctype").map(str::to_ascii_lowercase) {
+            Some(enctype) if enctype == MULTIPART || enctype == TEXT_PLAIN => enctype,
+            _ => String::from(URLENCODED),
+        };
+        Form {
+            id: element.id.clone(),
+            action,
+            method: method.to_string(),
+            enctype,
+            controls: vec![],
+        }
+    }
+
+    // set
+    // Fill in the control called "name" the way a user would, i.e. type the text, check the
+    // checkbox or radio with that value, or pick the option with that value or label
+    // ---- returns whether a control took the value
+    pub fn set(&mut self, name: &str, value: &str) -> bool {
+        let mut found = false;
+        let is_radio_group = self.controls.iter().any(|control| {
+            control.name == name && control.kind == ControlKind::Radio && control.value == value
+        });
+        for control in self
+            .controls
+            .iter_mut()
+            .filter(|control| control.name == name && !control.disabled)
+        {
+            match control.kind {
+                ControlKind::Text
+                | ControlKind::Password
+                | ControlKind::Hidden
+                | ControlKind::TextArea
+                    if !found =>
+                {
+                    control.value = value.to_string();
+                    found = true;
+                }
+                ControlKind::Checkbox if control.value == value => {
+                    control.checked = true;
+                    found = true;
+                }
+                ControlKind::Radio if is_radio_group => {
+                    control.checked = control.value == value;
+                    found = true;
+                }
+                ControlKind::Select => {
+                    let index = control
+                        .options
+                        .iter()
+                        .position(|option| option.value == value)
+                        .or_else(|| {
+                            control
+                                .options
+                                .iter()
+                                .position(|option| option.label == value)
+                        });
+                    if let Some(index) = index {
+                        control.select(index);
+                        found = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        found
+    }
+
+    // check
+    // Check or uncheck control "index", checking a radio unchecks the rest of its group
+    pub fn check(&mut self, index: usize, checked: bool) {
+        let (kind, name) = (self.controls[index].kind, self.controls[index].name.clone());
+        if kind == ControlKind::Radio && checked && !name.is_empty() {
+            for control in self
+                .controls
+                .iter_mut()
+                .filter(|control| control.kind == ControlKind::Radio && control.name == name)
+            {
+                control.checked = false;
+            }
+        }
+        self.controls[index].checked = checked;
+    }
+
+    // same_fields
+    // Whether both forms are the same form of the same page, i.e. filled in values of one still
+    // make sense for the other
+    pub fn same_fields(&self, other: &Form) -> bool {
+        self.action == other.action
+            && self.method == other.method
+            && self.controls.len() == other.controls.len()
+            && self
+                .controls
+                .iter()
+                .zip(&other.controls)
+                .all(|(a, b)| a.position == b.position && a.kind == b.kind && a.name == b.name)
+    }
+
+    // Index of the button an Enter in a text field presses, i.e. the first submit button
+    pub fn default_button(&self) -> Option<usize> {
+        self.controls
+            .iter()
+            .position(|control| matches!(control.kind, ControlKind::Submit | ControlKind::Image))
+    }
+
+    // entries
+    // The name/value pairs submitting the form sends, in document order, "submitter" is the
+    // index of the button that submitted it, HTML's "constructing the entry list"
+    pub fn entries(&self, submitter: Option<usize>) -> Vec<(String, String)> {
+        let mut entries = vec![];
+        for (index, control) in self.controls.iter().enumerate() {
+            if !control.is_submittable() {
+                continue;
+            }
+            match control.kind {
+                ControlKind::Submit if submitter == Some(index) => {
+                    entries.push((control.name.clone(), control.value.clone()));
+                }
+                ControlKind::Image if submitter == Some(index) => {
+                    entries.push((format!("{}.x", control.name), String::from("0")));
+                    entries.push((format!("{}.y", control.name), String::from("0")));
+                }
+                ControlKind::Submit | ControlKind::Image => {}
+                ControlKind::Checkbox | ControlKind::Radio if !control.checked => {}
+                ControlKind::Select => {
+                    for option in control.options.iter().filter(|option| option.selected) {
+                        entries.push((control.name.clone(), option.value.clone()));
+                    }
+                }
+                // Only the file name would go along, and no file can be picked here
+                ControlKind::File => entries.push((control.name.clone(), String::new())),
+                ControlKind::TextArea => {
+                    let value = control.value.replace("\r\n", "\n").replace('\n', "\r\n");
+                    entries.push((control.name.clone(), value));
+                }
+                _ => entries.push((control.name.clone(), control.value.clone())),
+            }
+        }
+        entries
+    }
+
+    // request
+    // The request submitting the form sends, GET puts the fields in the action's query, POST in
+    // the body encoded as the form's enctype
+    // ---- text goes out in "encoding", the page's, characters it can't hold turn into &#N;
+    //      references like browsers send them
+    pub fn request(
+        &self,
+        submitter: Option<usize>,
+        encoding: &'static Encoding,
+    ) -> Result<Request, String> {
+        let action = self
+            .action
+            .as_ref()
+            .map_err(|action| format!("Can't submit to '{action}', it isn't an http(s) URL"))?;
+        let entries = self.entries(submitter);
+        if self.method == "GET" {
+            let mut url = action.clone();
+            url.query = Some(urlencode(&entries, encoding));
+            url.fragment = None;
+            return Ok(Request::get(url));
+        }
+
+        let (content_type, body) = match self.enctype.as_str() {
+            MULTIPART => {
+                let boundary = format!(
+                    "----FormBoundary{:x}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |since| since.as_nanos())
+                );
+                (
+                    format!("{MULTIPART}; boundary={boundary}"),
+                    encoding
+                        .encode(&multipart(&entries, &boundary))
+                        .0
+                        .into_owned(),
+                )
+            }
+            TEXT_PLAIN => {
+                let body = entries
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}\r\n"))
+                    .collect::<String>();
+                (
+                    String::from(TEXT_PLAIN),
+                    encoding.encode(&body).0.into_owned(),
+                )
+            }
+            _ => (
+                String::from(URLENCODED),
+                urlencode(&entries, encoding).into_bytes(),
+            ),
+        };
+        let mut url = action.clone();
+        url.fragment = None;
+        Ok(Request::new("POST", url)
+            .header("Content-Type", &content_type)
+            .body(body))
+    }
+}
+
+// forms
+// Every form of a page along with its controls, controls outside of a <form> that name one
+// through their form="id" attribute are added to it
+pub fn forms(dom: &Dom, page_url: &Url) -> Vec<Form> {
+    let mut forms = vec![];
+    let mut owned_elsewhere = vec![];
+    let mut position = 0;
+    for node in &dom.children {
+        collect(
+            node,
+            page_url,
+            None,
+            &mut position,
+            &mut forms,
+            &mut owned_elsewhere,
+        );
+    }
+    for (form_id, control) in owned_elsewhere {
+        if let Some(form) = forms
+            .iter_mut()
+            .find(|form| form.id.as_deref() == Some(form_id.as_str()))
+        {
+            form.controls.push(control);
+        }
+    }
+    forms
+}
+
+fn collect(
+    node: &Node,
+    page_url: &Url,
+    current_form: Option<usize>,
+    position: &mut usize,
+    forms: &mut Vec<Form>,
+    owned_elsewhere: &mut Vec<(String, Control)>,
+) {
+    let Node::Element(element) = node else { return };
+    let mut current_form = current_form;
+    // Nested forms aren't allowed, the inner <form> tag is ignored like browsers do
+    if element.name.eq_ignore_ascii_case("form") && current_form.is_none() {
+        forms.push(Form::new(element, page_url));
+        current_form = Some(forms.len() - 1);
+    }
+
+    if let Some(mut control) = Control::from_element(element) {
+        control.position = *position;
+        *position += 1;
+        match (attribute(element, "form"), current_form) {
+            (Some(form_id), _) => owned_elsewhere.push((form_id.to_string(), control)),
+            (None, Some(index)) => forms[index].controls.push(control),
+            (None, None) => {}
+        }
+        // Nothing inside a control is a control of its own
+        return;
+    }
+    for child in &element.children {
+        collect(
+            child,
+            page_url,
+            current_form,
+            position,
+            forms,
+            owned_elsewhere,
+        );
+    }
+}
+
+// find
+// Form and control index of the control at "position" of the page, None when it's in no form
+pub fn find(forms: &[Form], position: usize) -> Option<(usize, usize)> {
+    forms.iter().enumerate().find_map(|(form_index, form)| {
+        form.controls
+            .iter()
+            .position(|control| control.position == position)
+            .map(|control_index| (form_index, control_index))
+    })
+}
+
+// is_control
+// Whether the element is a form control, controls are numbered in the order this is true for
+// elements of the page, nothing inside a control counts
+pub fn is_control(element: &Element) -> bool {
+    control_kind(element).is_some()
+}
+
+fn control_kind(element: &Element) -> Option<ControlKind> {
+    let kind = match element.name.to_ascii_lowercase().as_str() {
+        "input" => match attribute(element, "type")
+            .unwrap_or("text")
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "password" => ControlKind::Password,
+            "hidden" => ControlKind::Hidden,
+            "checkbox" => ControlKind::Checkbox,
+            "radio" => ControlKind::Radio,
+            "submit" => ControlKind::Submit,
+            "image" => ControlKind::Image,
+            "file" => ControlKind::File,
+            "button" | "reset" => ControlKind::Button,
+            _ => ControlKind::Text,
+        },
+        "button" => match attribute(element, "type")
+            .unwrap_or("submit")
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "button" | "reset" => ControlKind::Button,
+            _ => ControlKind::Submit,
+        },
+        "select" => ControlKind::Select,
+        "textarea" => ControlKind::TextArea,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+// Attribute lookup ignoring the case of its name, valueless attributes give Some("")
+// ---- html_parser glues a valueless attribute to the next one, e.g. `disabled value="x"` comes
+//      out as the key "disabled value", only the last name of such a key has the value
+fn attribute<'a>(element: &'a Element, name: &str) -> Option<&'a str> {
+    element.attributes.iter().find_map(|(key, value)| {
+        let mut names = key.split_whitespace().rev();
+        if names.next()?.eq_ignore_ascii_case(name) {
+            Some(value.as_deref().unwrap_or_default())
+        } else {
+            names
+                .any(|key| key.eq_ignore_ascii_case(name))
+                .then_some("")
+        }
+    })
+}
+
+// All the text inside an element with its whitespace collapsed, except for a <textarea>
+// ---- html_parser trims text nodes, so the parts are put back together with a space
+fn text_content(element: &Element) -> String {
+    let mut parts = vec![];
+    for child in &element.children {
+        match child {
+            Node::Text(part) => parts.push(part.clone()),
+            Node::Element(child) => parts.push(text_content(child)),
+            _ => {}
+        }
+    }
+    let text = parts.join(" ");
+    if element.name.eq_ignore_ascii_case("textarea") {
+        text
+    } else {
+        text.split_whitespace().collect::<Vec<&str>>().join(" ")
+    }
+}
+
+// The <option>s of a <select>, including the ones inside an <optgroup>
+fn options(element: &Element) -> Vec<SelectOption> {
+    let mut options = vec![];
+    for child in &element.children {
+        let Node::Element(child) = child else {
+            continue;
+        };
+        if child.name.eq_ignore_ascii_case("option") {
+            let label = attribute(child, "label").map_or_else(|| text_content(child), String::from);
+            options.push(SelectOption {
+                value: attribute(child, "value").map_or_else(|| text_content(child), String::from),
+                label,
+                selected: attribute(child, "selected").is_some(),
+            });
+        } else if child.name.eq_ignore_ascii_case("optgroup") {
+            options.append(&mut self::options(child));
+        }
+    }
+    options
+}
+
+// describe_control
+// One line summary of a form control and its current value, passwords are masked
+pub fn describe_control(control: &Control) -> String {
+    match control.kind {
+        ControlKind::Password => format!(
+            "{}={}",
+            control.name,
+            "*".repeat(control.value.chars().count())
+        ),
+        ControlKind::Hidden => format!("{}={:?} (hidden)", control.name, control.value),
+        ControlKind::Checkbox => format!(
+            "[{}] {}={}",
+            if control.checked { "x" } else { " " },
+            control.name,
+            control.value
+        ),
+        ControlKind::Radio => format!(
+            "({}) {}={}",
+            if control.checked { "*" } else { " " },
+            control.name,
+            control.value
+        ),
+        ControlKind::Select => format!(
+            "{}={{{}}}",
+            control.name,
+            control
+                .options
+                .iter()
+                .map(|option| format!("{}{}", if option.selected { "*" } else { "" }, option.value))
+                .collect::<Vec<String>>()
+                .join("|")
+        ),
+        ControlKind::Submit | ControlKind::Image | ControlKind::Button => {
+            format!(
+                "<{}{}>",
+                control.label,
+                if control.name.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {}={}", control.name, control.value)
+                }
+            )
+        }
+        ControlKind::File => format!("{} (file, not supported)", control.name),
+        ControlKind::Text | ControlKind::TextArea => {
+            format!("{}={:?}", control.name, control.value)
+        }
+    }
+}
+
+// WidgetKey -> what a key press did to the focused form widget
+pub enum WidgetKey {
+    Changed,
+    // Holds the command that submits the form
+    Submit(String),
+    Ignored,
+}
+
+// widget_text
+// How a form control looks in the page view, "option_cursor" marks the option a focused
+// multiple select is on
+pub fn widget_text(control: &Control, option_cursor: Option<usize>) -> String {
+    let mark = |on: bool, mark: &str| {
+        if on {
+            mark.to_string()
+        } else {
+            String::from(" ")
+        }
+    };
+    match control.kind {
+        ControlKind::Text => format!("[{:_<20}]", control.value),
+        ControlKind::Password => format!("[{:_<20}]", "*".repeat(control.value.chars().count())),
+        ControlKind::TextArea => format!("[{:_<20}]", control.value.replace('\n', "\\n")),
+        ControlKind::Checkbox => format!("[{}]", mark(control.checked, "x")),
+        ControlKind::Radio => format!("({})", mark(control.checked, "*")),
+        ControlKind::Select if control.multiple => format!(
+            "<{}>",
+            control
+                .options
+                .iter()
+                .enumerate()
+                .map(|(index, option)| format!(
+                    "{}[{}] {}",
+                    mark(option_cursor == Some(index), ">"),
+                    mark(option.selected, "x"),
+                    option.label
+                ))
+                .collect::<Vec<String>>()
+                .join(" ")
+        ),
+        ControlKind::Select => format!(
+            "<{} v>",
+            control
+                .options
+                .iter()
+                .find(|option| option.selected)
+                .map_or("", |option| &option.label)
+        ),
+        ControlKind::Submit | ControlKind::Image | ControlKind::Button => {
+            format!("[ {} ]", control.label)
+        }
+        ControlKind::File => String::from("[file upload not supported]"),
+        ControlKind::Hidden => String::new(),
+    }
+}
+
+// edit_widget
+// Apply a key press to control "index" of "form", the page's form number "form_index"
+// ---- text fields take every printable key, only the arrow keys move away from them
+// ---- Enter in a text field submits the form like its first submit button would
+pub fn edit_widget(
+    form: &mut Form,
+    form_index: usize,
+    index: usize,
+    option_cursor: &mut usize,
+    ch: i32,
+) -> WidgetKey {
+    let control = &mut form.controls[index];
+    match (control.kind, ch) {
+        (ControlKind::Text | ControlKind::Password, 10) => {
+            WidgetKey::Submit(format!("submit {form_index}"))
+        }
+        (ControlKind::TextArea, 10) => {
+            control.value.push('\n');
+            WidgetKey::Changed
+        }
+        (ControlKind::Text | ControlKind::Password | ControlKind::TextArea, 32..=126) => {
+            control.value.push(ch as u8 as char);
+            WidgetKey::Changed
+        }
+        (
+            ControlKind::Text | ControlKind::Password | ControlKind::TextArea,
+            KEY_BACKSPACE | 127 | 8,
+        ) => {
+            control.value.pop();
+            WidgetKey::Changed
+        }
+        (ControlKind::Checkbox, 10 | 32) => {
+            let checked = !control.checked;
+            form.check(index, checked);
+            WidgetKey::Changed
+        }
+        (ControlKind::Radio, 10 | 32) => {
+            form.check(index, true);
+            WidgetKey::Changed
+        }
+        (ControlKind::Select, KEY_LEFT | KEY_RIGHT | 10 | 32) if !control.options.is_empty() => {
+            let count = control.options.len();
+            let current = if control.multiple {
+                *option_cursor
+            } else {
+                control
+                    .options
+                    .iter()
+                    .position(|option| option.selected)
+                    .unwrap_or(0)
+            };
+            let next = match ch {
+                KEY_LEFT => (current + count - 1) % count,
+                KEY_RIGHT => (current + 1) % count,
+                // Enter and Space toggle a multiple select's option, a single select moves on
+                _ if control.multiple => current,
+                _ => (current + 1) % count,
+            };
+            if control.multiple && matches!(ch, KEY_LEFT | KEY_RIGHT) {
+                *option_cursor = next;
+            } else {
+                control.select(next);
+            }
+            WidgetKey::Changed
+        }
+        (ControlKind::Submit | ControlKind::Image, 10 | 32) => {
+            let mut submit = format!("submit {form_index}");
+            // The button itself only goes along when it has a name
+            if !control.name.is_empty() {
+                let button = format!("{}={}", control.name, control.value);
+                submit.push_str(&format!(
+                    " \"{}\"",
+                    button.replace('\\', "\\\\").replace('"', "\\\"")
+                ));
+            }
+            WidgetKey::Submit(submit)
+        }
+        _ => WidgetKey::Ignored,
+    }
+}
+
+// urlencode
+// application/x-www-form-urlencoded serialization of the bytes "encoding" turns the text into,
+// spaces turn into '+'
+pub fn urlencode(entries: &[(String, String)], encoding: &'static Encoding) -> String {
+    let encode = |text: &str| {
+        let mut encoded = String::new();
+        for &byte in encoding.encode(text).0.iter() {
+            match byte {
+                b' ' => encoded.push('+'),
+                _ if byte.is_ascii_alphanumeric() || b"*-._".contains(&byte) => {
+                    encoded.push(byte as char);
+                }
+                _ => encoded.push_str(&format!("%{byte:02X}")),
+            }
+        }
+        encoded
+    };
+    entries
+        .iter()
+        .map(|(name, value)| format!("{}={}", encode(name), encode(value)))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+// multipart/form-data body, RFC 7578, file controls go along as empty fields
+fn multipart(entries: &[(String, String)], boundary: &str) -> String {
+    let mut body = String::new();
+    for (name, value) in entries {
+        let name = name
+            .replace('"', "%22")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A");
+        body.push_str(&format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+        ));
+    }
+    body.push_str(&format!("--{boundary}--\r\n"));
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{UTF_8, WINDOWS_1252};
+
+    fn page_forms(html: &str) -> Vec<Form> {
+        forms(
+            &Dom::parse(html).unwrap(),
+            &Url::parse("http://example.com/dir/page#top").unwrap(),
+        )
+    }
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_forms_and_their_controls() {
+        let forms = page_forms(
+            r#"<form action="search" method="post" enctype="Multipart/Form-Data">
+                 <input name="q" value="rust">
+                 <input type="checkbox" name="safe" checked>
+                 <input type="radio" name="size" value="s">
+                 <input type="radio" name="size" value="m" checked>
+                 <textarea name="notes">
+line</textarea>
+                 <button name="go" value="1">Search <b>now</b></button>
+               </form>
+               <form><form><input name="inner"></form></form>
+               <input name="outside">
+               <input name="owned" form="late" value="x">
+               <form id="late"></form>"#,
+        );
+        assert_eq!(forms.len(), 3);
+        let form = &forms[0];
+        assert_eq!(
+            form.action,
+            Ok(Url::parse("http://example.com/dir/search").unwrap())
+        );
+        assert_eq!(
+            (form.method.as_str(), form.enctype.as_str()),
+            ("POST", MULTIPART)
+        );
+        assert_eq!(form.controls[1].value, "on");
+        assert_eq!(form.controls[4].value, "line");
+        assert_eq!(form.controls[5].kind, ControlKind::Submit);
+        assert_eq!(form.controls[5].label, "Search now");
+        assert_eq!(
+            form.entries(None),
+            pairs(&[
+                ("q", "rust"),
+                ("safe", "on"),
+                ("size", "m"),
+                ("notes", "line")
+            ])
+        );
+
+        // The nested form tag is ignored, its control goes to the outer form
+        assert_eq!(
+            forms[1].action,
+            Ok(Url::parse("http://example.com/dir/page#top").unwrap())
+        );
+        assert_eq!(forms[1].method, "GET");
+        assert_eq!(forms[1].controls[0].name, "inner");
+        assert_eq!(forms[2].controls[0].name, "owned");
+        assert_eq!(find(&forms, forms[2].controls[0].position), Some((2, 0)));
+        assert_eq!(find(&forms, 7), None);
+    }
+
+    #[test]
+    fn selects_start_on_an_option() {
+        let forms = page_forms(
+            r#"<form>
+                 <select name="one"><option>a</option><option value="b" label="Bee">x</option></select>
+                 <select name="two"><option selected>a</option><option selected>b</option></select>
+                 <select name="many" multiple><optgroup><option>a</option></optgroup></select>
+               </form>"#,
+        );
+        let mut form = forms[0].clone();
+        assert_eq!(form.entries(None), pairs(&[("one", "a"), ("two", "b")]));
+        assert!(form.set("one", "Bee"));
+        assert!(form.set("many", "a"));
+        assert!(!form.set("many", "z"));
+        assert_eq!(
+            form.entries(None),
+            pairs(&[("one", "b"), ("two", "b"), ("many", "a")])
+        );
+    }
+
+    #[test]
+    fn fills_in_like_a_user() {
+        let forms = page_forms(
+            r#"<form>
+                 <input name="user">
+                 <input type="radio" name="plan" value="free" checked>
+                 <input type="radio" name="plan" value="paid">
+                 <input type="checkbox" name="tos" value="yes">
+                 <input name="locked" value="same" disabled>
+                 <input type="submit" name="go" value="Sign up">
+                 <input type="image" name="pic">
+               </form>"#,
+        );
+        let mut form = forms[0].clone();
+        assert!(form.set("user", "ann"));
+        assert!(form.set("plan", "paid"));
+        assert!(form.set("tos", "yes"));
+        assert!(!form.set("locked", "changed"));
+        assert!(!form.set("plan", "gold"));
+        assert_eq!(form.default_button(), Some(5));
+        assert_eq!(
+            form.entries(Some(5)),
+            pairs(&[
+                ("user", "ann"),
+                ("plan", "paid"),
+                ("tos", "yes"),
+                ("go", "Sign up")
+            ])
+        );
+        form.check(1, true);
+        assert!(!form.controls[2].checked);
+        let clicked = form.entries(Some(6));
+        assert_eq!(
+            clicked[clicked.len() - 2..],
+            pairs(&[("pic.x", "0"), ("pic.y", "0")])
+        );
+        assert!(form.same_fields(&forms[0]));
+    }
+
+    #[test]
+    fn submits_get_in_the_query_and_post_in_the_body() {
+        let forms = page_forms(
+            r#"<form action="/find?old=1#frag"><input name="q" value="a b&c=d/é"></form>
+               <form method="POST"><textarea name="t">one
+two</textarea></form>
+               <form method="post" enctype="text/plain"><input name="a" value="1"></form>"#,
+        );
+        let request = forms[0].request(None, UTF_8).unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(
+            request.url.to_string(),
+            "http://example.com/find?q=a+b%26c%3Dd%2F%C3%A9"
+        );
+
+        let request = forms[1].request(None, UTF_8).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.url.to_string(), "http://example.com/dir/page");
+        assert_eq!(
+            request.headers.get("Content-Type"),
+            Some("application/x-www-form-urlencoded")
+        );
+        assert_eq!(request.body, b"t=one%0D%0Atwo");
+
+        let request = forms[2].request(None, UTF_8).unwrap();
+        assert_eq!(request.body, b"a=1\r\n");
+    }
+
+    #[test]
+    fn writes_multipart_bodies() {
+        let entries = [
+            (
+                String::from("a\"b\r\n"),
+                String::from("line one\r\nline two"),
+            ),
+            (String::from("empty"), String::new()),
+        ];
+        assert_eq!(
+            multipart(&entries, "XYZ"),
+            "--XYZ\r\nContent-Disposition: form-data; name=\"a%22b%0D%0A\"\r\n\r\n\
+             line one\r\nline two\r\n\
+             --XYZ\r\nContent-Disposition: form-data; name=\"empty\"\r\n\r\n\r\n\
+             --XYZ--\r\n"
+        );
+    }
+
+    #[test]
+    fn describes_and_draws_controls() {
+        let forms = page_forms(
+            r#"<form>
+                 <input type="password" name="pw" value="abc">
+                 <input type="checkbox" name="tos" value="yes" checked>
+                 <select name="one"><option>a</option><option selected>b</option></select>
+                 <select name="many" multiple><option selected>a</option><option>b</option></select>
+                 <input type="submit" name="go" value="1">
+                 <input type="file" name="cv">
+               </form>"#,
+        );
+        let controls = &forms[0].controls;
+        let described: Vec<String> = controls.iter().map(describe_control).collect();
+        assert_eq!(
+            described,
+            [
+                "pw=***",
+                "[x] tos=yes",
+                "one={a|*b}",
+                "many={*a|b}",
+                "<1 go=1>",
+                "cv (file, not supported)"
+            ]
+        );
+        assert_eq!(widget_text(&controls[0], None), format!("[{:_<20}]", "***"));
+        assert_eq!(widget_text(&controls[1], None), "[x]");
+        assert_eq!(widget_text(&controls[2], None), "<b v>");
+        assert_eq!(widget_text(&controls[3], Some(1)), "< [x] a >[ ] b>");
+        assert_eq!(widget_text(&controls[4], None), "[ 1 ]");
+    }
+
+    #[test]
+    fn edits_widgets_with_keys() {
+        let mut form = page_forms(
+            r#"<form>
+                 <input name="q" value="ab">
+                 <input type="radio" name="r" value="1" checked>
+                 <input type="radio" name="r" value="2">
+                 <select name="s"><option>a</option><option>b</option></select>
+                 <input type="submit" name="go" value='say "hi"'>
+               </form>"#,
+        )
+        .remove(0);
+        let mut cursor = 0;
+        let mut press = |form: &mut Form, index, ch| edit_widget(form, 3, index, &mut cursor, ch);
+
+        assert!(matches!(
+            press(&mut form, 0, 'c' as i32),
+            WidgetKey::Changed
+        ));
+        assert!(matches!(press(&mut form, 0, 127), WidgetKey::Changed));
+        assert!(matches!(press(&mut form, 0, 127), WidgetKey::Changed));
+        assert_eq!(form.controls[0].value, "a");
+        assert!(
+            matches!(press(&mut form, 0, 10), WidgetKey::Submit(submit) if submit == "submit 3")
+        );
+
+        assert!(matches!(press(&mut form, 2, 32), WidgetKey::Changed));
+        assert!(!form.controls[1].checked && form.controls[2].checked);
+
+        assert!(matches!(press(&mut form, 3, KEY_LEFT), WidgetKey::Changed));
+        assert_eq!(
+            form.entries(None)[2],
+            (String::from("s"), String::from("b"))
+        );
+        assert!(matches!(
+            press(&mut form, 3, 'x' as i32),
+            WidgetKey::Ignored
+        ));
+
+        assert!(matches!(
+            press(&mut form, 4, 10),
+            WidgetKey::Submit(submit) if submit == r#"submit 3 "go=say \"hi\"""#
+        ));
+    }
+
+    #[test]
+    fn refuses_actions_that_arent_http() {
+        let forms = page_forms(
+            r#"<form action="  "></form>
+               <form action="javascript:go()"></form>
+               <form action="ftp://example.com/up"></form>"#,
+        );
+        assert!(forms[0].request(None, UTF_8).is_ok());
+        assert_eq!(forms[1].action, Err(String::from("javascript:go()")));
+        assert!(forms[1].request(None, UTF_8).is_err());
+        assert!(forms[2].request(None, UTF_8).is_err());
+    }
+
+    #[test]
+    fn encodes_text_as_the_page() {
+        let forms = page_forms(
+            r#"<form><input name="q" value="café €"></form>
+               <form method="post"><input name="q" value="ü 日"></form>"#,
+        );
+        let request = forms[0].request(None, WINDOWS_1252).unwrap();
+        assert_eq!(request.url.query.as_deref(), Some("q=caf%E9+%80"));
+        // What the page's encoding can't hold goes as a character reference
+        let request = forms[1].request(None, WINDOWS_1252).unwrap();
+        assert_eq!(request.body, b"q=%FC+%26%2326085%3B");
+    }
+}
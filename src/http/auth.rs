@@ -0,0 +1,748 @@
+use super::HeaderMap;
+use crate::url::{self, Url};
+use base64::{engine::general_purpose, Engine as _};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::{fs, io, path::Path};
+use zeroize::Zeroizing;
+
+// Challenge
+// One authentication scheme a server offers through WWW-Authenticate, RFC 7235 section 2.1
+// ---- parameter names are lowercased, quoted values are unescaped
+// ---- "token68" holds the single opaque value some schemes use instead of parameters
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Challenge {
+    pub scheme: String,
+    pub params: Vec<(String, String)>,
+    pub token68: Option<String>,
+}
+
+impl Challenge {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn realm(&self) -> Option<&str> {
+        self.param("realm")
+    }
+
+    // strength
+    // How much the scheme protects the password, None when it can't be answered
+    // ---- Digest needs a nonce, qop=auth (or no qop at all, RFC 2069) and a known algorithm
+    pub fn strength(&self) -> Option<u8> {
+        match self.scheme.to_ascii_lowercase().as_str() {
+            "basic" => Some(1),
+            "digest" => {
+                self.param("nonce")?;
+                if self
+                    .param("qop")
+                    .is_some_and(|qop| !qop.split(',').any(|qop| qop.trim() == "auth"))
+                {
+                    return None;
+                }
+                match self.algorithm()? {
+                    Algorithm::Md5 => Some(2),
+                    Algorithm::Sha256 => Some(3),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // authorization
+    // Value of the Authorization header answering this challenge for a "method" request to
+    // "uri" (the request target), None when the scheme isn't supported
+    // ---- "nc" counts the requests sent with the same Digest nonce, starting from 1
+    // ---- strings holding the password are wiped once hashed or encoded
+    pub fn authorization(
+        &self,
+        method: &str,
+        uri: &str,
+        credentials: &Credentials,
+        nc: u32,
+    ) -> Option<String> {
+        self.answer(method, uri, credentials, nc, &cnonce())
+    }
+
+    // authorization with the client nonce given
+    fn answer(
+        &self,
+        method: &str,
+        uri: &str,
+        credentials: &Credentials,
+        nc: u32,
+        cnonce: &str,
+    ) -> Option<String> {
+        self.strength()?;
+        let (username, password) = (credentials.username.as_str(), credentials.password.as_str());
+        if self.scheme.eq_ignore_ascii_case("basic") {
+            let credentials = Zeroizing::new(format!("{username}:{password}"));
+            let credentials =
+                Zeroizing::new(general_purpose::STANDARD.encode(credentials.as_bytes()));
+            return Some(format!("Basic {}", *credentials));
+        }
+
+        let algorithm = self.algorithm()?;
+        let realm = self.realm().unwrap_or_default();
+        let nonce = self.param("nonce")?;
+        let qop = self.param("qop").map(|_| "auth");
+        let nc = format!("{nc:08x}");
+
+        // RFC 7616 section 3.4.2, the "-sess" variants mix both nonces into A1
+        let mut ha1 = algorithm.hash(&Zeroizing::new(format!("{username}:{realm}:{password}")));
+        if self.is_session() {
+            ha1 = algorithm.hash(&format!("{ha1}:{nonce}:{cnonce}"));
+        }
+        let ha2 = algorithm.hash(&format!("{method}:{uri}"));
+        let response = match qop {
+            Some(qop) => algorithm.hash(&format!("{ha1}:{nonce}:{nc}:{cnonce}:{qop}:{ha2}")),
+            None => algorithm.hash(&format!("{ha1}:{nonce}:{ha2}")),
+        };
+
+        // userhash=true asks for the username to be hashed as well, section 3.4.4
+        let username = match self.param("userhash") {
+            Some(userhash) if userhash.eq_ignore_ascii_case("true") => {
+                algorithm.hash(&format!("{username}:{realm}"))
+            }
+            _ => username.to_string(),
+        };
+        let mut fields = vec![
+            format!("username={}", quote(&username)),
+            format!("realm={}", quote(realm)),
+            format!("uri={}", quote(uri)),
+            format!("algorithm={}", self.param("algorithm").unwrap_or("MD5")),
+            format!("nonce={}", quote(nonce)),
+        ];
+        if let Some(qop) = qop {
+            fields.push(format!("qop={qop}"));
+            fields.push(format!("nc={nc}"));
+            fields.push(format!("cnonce={}", quote(cnonce)));
+        }
+        fields.push(format!("response={}", quote(&response)));
+        if let Some(opaque) = self.param("opaque") {
+            fields.push(format!("opaque={}", quote(opaque)));
+        }
+        if self
+            .param("userhash")
+            .is_some_and(|userhash| userhash.eq_ignore_ascii_case("true"))
+        {
+            fields.push(String::from("userhash=true"));
+        }
+        Some(format!("Digest {}", fields.join(", ")))
+    }
+
+    // Missing means MD5, RFC 7616 section 3.3
+    fn algorithm(&self) -> Option<Algorithm> {
+        let algorithm = self
+            .param("algorithm")
+            .unwrap_or("MD5")
+            .to_ascii_uppercase();
+        match algorithm.trim_end_matches("-SESS") {
+            "MD5" => Some(Algorithm::Md5),
+            "SHA-256" => Some(Algorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    fn is_session(&self) -> bool {
+        self.param("algorithm")
+            .is_some_and(|algorithm| algorithm.to_ascii_uppercase().ends_with("-SESS"))
+    }
+
+    // Whether the server only turned the request down because the Digest nonce ran out, the
+    // same credentials work with the new one, RFC 7616 section 3.3
+    pub fn is_stale(&self) -> bool {
+        self.param("stale")
+            .is_some_and(|stale| stale.eq_ignore_ascii_case("true"))
+    }
+}
+
+// Credentials
+// A username and password, both wiped from memory when dropped
+#[derive(Clone, Default)]
+pub struct Credentials {
+    pub username: Zeroizing<String>,
+    pub password: Zeroizing<String>,
+}
+
+impl Credentials {
+    pub fn new(username: &str, password: &str) -> Self {
+        Credentials {
+            username: Zeroizing::new(username.to_string()),
+            password: Zeroizing::new(password.to_string()),
+        }
+    }
+
+    // from_userinfo
+    // Credentials of a "user:password" URL userinfo, percent escapes decoded, None without a user
+    pub fn from_userinfo(userinfo: &str) -> Option<Self> {
+        let (username, password) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+        let credentials = Credentials {
+            username: Zeroizing::new(url::percent_decode(username)),
+            password: Zeroizing::new(url::percent_decode(password)),
+        };
+        (!credentials.username.is_empty()).then_some(credentials)
+    }
+}
+
+// The password never goes into logs or debug dumps
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &*self.username)
+            .field("password", &"***")
+            .finish()
+    }
+}
+
+// CredentialCache
+// Credentials the user gave for each protection space (scheme, host, port and realm), kept for
+// the session and shared by all clones of a Client
+#[derive(Debug, Default)]
+pub struct CredentialCache {
+    entries: Vec<CacheEntry>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    scheme: String,
+    host: String,
+    port: u16,
+    realm: String,
+    // Latest challenge of the realm, Digest answers are computed from its nonce
+    challenge: Challenge,
+    credentials: Credentials,
+    // Path prefixes the credentials are sent to without waiting for a 401
+    paths: Vec<String>,
+    // Requests sent with the current Digest nonce
+    nc: u32,
+}
+
+impl CacheEntry {
+    fn is_space(&self, url: &Url, realm: &str) -> bool {
+        self.scheme == url.scheme
+            && self.host.eq_ignore_ascii_case(&url.host)
+            && self.port == url.port
+            && self.realm == realm
+    }
+
+    fn covers(&self, url: &Url) -> bool {
+        self.scheme == url.scheme
+            && self.host.eq_ignore_ascii_case(&url.host)
+            && self.port == url.port
+            && self
+                .paths
+                .iter()
+                .any(|path| url.path.starts_with(path.as_str()))
+    }
+}
+
+impl CredentialCache {
+    pub fn new() -> Self {
+        CredentialCache::default()
+    }
+
+    // insert
+    // Keep "credentials" as the answer to "challenge", which came back for a request to "url"
+    // ---- they are sent ahead of time to everything under the directory of "url", RFC 7617
+    //      section 2.2, and to the URIs a Digest "domain" lists
+    pub fn insert(&mut self, url: &Url, challenge: &Challenge, credentials: Credentials) {
+        let realm = challenge.realm().unwrap_or_default();
+        let mut paths = match self
+            .entries
+            .iter()
+            .position(|entry| entry.is_space(url, realm))
+        {
+            Some(index) => self.entries.remove(index).paths,
+            None => vec![],
+        };
+        let directory = &url.path[..url.path.rfind('/').map_or(0, |index| index + 1)];
+        paths.push(if directory.is_empty() {
+            String::from("/")
+        } else {
+            directory.to_string()
+        });
+        for uri in challenge
+            .param("domain")
+            .unwrap_or_default()
+            .split_whitespace()
+        {
+            match url.join(uri) {
+                Ok(domain)
+                    if domain.scheme == url.scheme
+                        && domain.host == url.host
+                        && domain.port == url.port =>
+                {
+                    paths.push(domain.path)
+                }
+                _ => {}
+            }
+        }
+        paths.sort();
+        paths.dedup();
+
+        self.entries.push(CacheEntry {
+            scheme: url.scheme.clone(),
+            host: url.host.to_ascii_lowercase(),
+            port: url.port,
+            realm: realm.to_string(),
+            challenge: challenge.clone(),
+            credentials,
+            paths,
+            nc: 0,
+        });
+    }
+
+    // Credentials kept for the realm of "url"'s server
+    pub fn get(&self, url: &Url, realm: &str) -> Option<&Credentials> {
+        self.entries
+            .iter()
+            .find(|entry| entry.is_space(url, realm))
+            .map(|entry| &entry.credentials)
+    }
+
+    // Whether the credentials of the realm already went out with a request to "url"
+    pub fn covers(&self, url: &Url, realm: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.is_space(url, realm) && entry.covers(url))
+    }
+
+    pub fn remove(&mut self, url: &Url, realm: &str) {
+        self.entries.retain(|entry| !entry.is_space(url, realm));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // authorization
+    // Authorization header for a "method" request to "url", sent before the server asks for it
+    // ---- the longest matching path wins when several realms cover "url"
+    pub fn authorization(&mut self, method: &str, url: &Url) -> Option<Zeroizing<String>> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .filter(|entry| entry.covers(url))
+            .max_by_key(|entry| {
+                entry
+                    .paths
+                    .iter()
+                    .filter(|path| url.path.starts_with(path.as_str()))
+                    .map(String::len)
+                    .max()
+            })?;
+        entry.nc += 1;
+        entry
+            .challenge
+            .authorization(method, &url.request_target(), &entry.credentials, entry.nc)
+            .map(Zeroizing::new)
+    }
+}
+
+// netrc
+// Credentials the .netrc file at "path" has for "host", the "default" entry is used when no
+// "machine" matches
+// ---- tokens are separated by whitespace, quoted tokens may hold spaces as curl allows,
+//      "macdef" macros run until the next blank line and are skipped
+pub fn netrc(path: &Path, host: &str) -> io::Result<Option<Credentials>> {
+    let file = Zeroizing::new(fs::read_to_string(path)?);
+    let mut rest = file.as_str();
+    // Every machine or default entry, and whether it is the one for "host"
+    let mut entries: Vec<(bool, Credentials)> = vec![];
+    while let Some(token) = netrc_token(&mut rest) {
+        match token.as_str() {
+            "machine" => {
+                let machine = netrc_token(&mut rest).unwrap_or_default();
+                entries.push((machine.eq_ignore_ascii_case(host), Credentials::default()));
+            }
+            "default" => entries.push((true, Credentials::default())),
+            "login" => {
+                let login = netrc_token(&mut rest).unwrap_or_default();
+                if let Some((_, credentials)) = entries.last_mut() {
+                    credentials.username = login;
+                }
+            }
+            "password" => {
+                let password = netrc_token(&mut rest).unwrap_or_default();
+                if let Some((_, credentials)) = entries.last_mut() {
+                    credentials.password = password;
+                }
+            }
+            "account" => {
+                netrc_token(&mut rest);
+            }
+            "macdef" => rest = rest.split_once("\n\n").map_or("", |(_, after)| after),
+            _ => {}
+        }
+    }
+    Ok(entries
+        .into_iter()
+        .find(|(matches, credentials)| *matches && !credentials.username.is_empty())
+        .map(|(_, credentials)| credentials))
+}
+
+fn netrc_token(rest: &mut &str) -> Option<Zeroizing<String>> {
+    *rest = rest.trim_start();
+    let mut token = Zeroizing::new(String::new());
+    let mut chars = rest.char_indices();
+    match chars.next()? {
+        (_, '"') => {
+            while let Some((index, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        *rest = &rest[index + 1..];
+                        return Some(token);
+                    }
+                    '\\' => token.extend(chars.next().map(|(_, c)| c)),
+                    _ => token.push(c),
+                }
+            }
+            *rest = "";
+        }
+        _ => {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            token.push_str(&rest[..end]);
+            *rest = &rest[end..];
+        }
+    }
+    Some(token)
+}
+
+enum Algorithm {
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    // Lowercase hex digest of "input"
+    fn hash(&self, input: &str) -> String {
+        let digest = match self {
+            Algorithm::Md5 => Md5::digest(input.as_bytes()).to_vec(),
+            Algorithm::Sha256 => Sha256::digest(input.as_bytes()).to_vec(),
+        };
+        digest.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+// parse
+// Every challenge of the WWW-Authenticate (or Proxy-Authenticate) headers called "name",
+// RFC 7235 section 4.1
+// ---- a header may hold several challenges, commas separate both challenges and parameters so
+//      a new challenge starts at a token that isn't followed by "="
+pub fn parse(headers: &HeaderMap, name: &str) -> Vec<Challenge> {
+    let mut challenges = vec![];
+    for value in headers.get_all(name) {
+        let mut rest = value;
+        loop {
+            rest = rest.trim_start_matches([' ', '\t', ',']);
+            if rest.is_empty() {
+                break;
+            }
+            let Some((scheme, after)) = token(rest) else {
+                // Not a challenge, skip to the next list element
+                rest = rest.split_once(',').map_or("", |(_, after)| after);
+                continue;
+            };
+            rest = after;
+            let mut challenge = Challenge {
+                scheme: scheme.to_string(),
+                params: vec![],
+                token68: None,
+            };
+
+            if let Some((token68, after)) = token68(rest) {
+                challenge.token68 = Some(token68.to_string());
+                rest = after;
+            } else {
+                while let Some((name, value, after)) = auth_param(rest) {
+                    challenge.params.push((name.to_ascii_lowercase(), value));
+                    rest = after;
+                }
+            }
+            challenges.push(challenge);
+        }
+    }
+    challenges
+}
+
+// strongest
+// The supported challenge that protects the password best, the first one offered wins a tie
+pub fn strongest(challenges: &[Challenge]) -> Option<&Challenge> {
+    challenges
+        .iter()
+        .filter_map(|challenge| Some((challenge.strength()?, challenge)))
+        .rev()
+        .max_by_key(|(strength, _)| *strength)
+        .map(|(_, challenge)| challenge)
+}
+
+fn is_tchar(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+// Leading token of "input" and what follows it
+fn token(input: &str) -> Option<(&str, &str)> {
+    let end = input.find(|c: char| !is_tchar(c)).unwrap_or(input.len());
+    (end > 0).then(|| input.split_at(end))
+}
+
+// token68 = 1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"=", it has to be the
+// only thing before the next comma
+fn token68(input: &str) -> Option<(&str, &str)> {
+    let input_start = input.trim_start_matches([' ', '\t']);
+    let end = input_start
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "-._~+/".contains(c)))
+        .unwrap_or(input_start.len());
+    if end == 0 {
+        return None;
+    }
+    let end = end + input_start[end..].len() - input_start[end..].trim_start_matches('=').len();
+    let (token68, rest) = input_start.split_at(end);
+    let after = rest.trim_start_matches([' ', '\t']);
+    (after.is_empty() || after.starts_with(',')).then_some((token68, rest))
+}
+
+// auth-param = token BWS "=" BWS ( token / quoted-string ), with any list commas before it
+fn auth_param(input: &str) -> Option<(&str, String, &str)> {
+    let (name, rest) = token(input.trim_start_matches([' ', '\t', ',']))?;
+    let rest = rest.trim_start_matches([' ', '\t']).strip_prefix('=')?;
+    let rest = rest.trim_start_matches([' ', '\t']);
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => return Some((name, value, &quoted[index + 1..])),
+                '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                _ => value.push(c),
+            }
+        }
+        // Unterminated, take what is there
+        Some((name, value, ""))
+    } else {
+        let (value, rest) = token(rest).unwrap_or(("", rest));
+        Some((name, value.to_string(), rest))
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// A fresh client nonce, falls back to the clock if the system has no randomness to give
+fn cnonce() -> String {
+    let mut bytes = [0u8; 16];
+    if getrandom::getrandom(&mut bytes).is_err() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos());
+        bytes = nanos.to_le_bytes();
+    }
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenges(values: &[&str]) -> Vec<Challenge> {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("WWW-Authenticate", value);
+        }
+        parse(&headers, "WWW-Authenticate")
+    }
+
+    fn digest(algorithm: &str) -> Challenge {
+        challenges(&[&format!(
+            "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", \
+             algorithm={algorithm}, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""
+        )])
+        .remove(0)
+    }
+
+    // Value of one Digest field, quotes removed
+    fn field<'a>(authorization: &'a str, name: &str) -> &'a str {
+        authorization
+            .trim_start_matches("Digest ")
+            .split(", ")
+            .find_map(|field| field.strip_prefix(name)?.strip_prefix('='))
+            .unwrap()
+            .trim_matches('"')
+    }
+
+    #[test]
+    fn splits_challenges_and_params() {
+        // RFC 7235 section 4.1
+        let parsed = challenges(&[
+            r#"Newauth realm="apps", type=1, title="Login to \"apps\"", Basic realm="simple""#,
+            "Negotiate abc+/==, Bearer",
+        ]);
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(parsed[0].scheme, "Newauth");
+        assert_eq!(parsed[0].realm(), Some("apps"));
+        assert_eq!(parsed[0].param("TYPE"), Some("1"));
+        assert_eq!(parsed[0].param("title"), Some(r#"Login to "apps""#));
+        assert_eq!(parsed[1].scheme, "Basic");
+        assert_eq!(parsed[1].realm(), Some("simple"));
+        assert_eq!(parsed[2].token68.as_deref(), Some("abc+/=="));
+        assert_eq!(parsed[3].scheme, "Bearer");
+        assert!(parsed[3].params.is_empty() && parsed[3].token68.is_none());
+    }
+
+    #[test]
+    fn picks_the_strongest_supported_scheme() {
+        let parsed = challenges(&[
+            "Basic realm=\"a\"",
+            "Digest realm=\"a\", nonce=\"n\", algorithm=MD5",
+            "Digest realm=\"a\", nonce=\"n\", algorithm=SHA-256",
+            "Digest realm=\"a\", nonce=\"n\", algorithm=SHA-512-256",
+            "Digest realm=\"a\", nonce=\"n\", qop=\"auth-int\"",
+            "Negotiate abc",
+        ]);
+        assert_eq!(strongest(&parsed), Some(&parsed[2]));
+        assert_eq!(strongest(&parsed[..2]), Some(&parsed[1]));
+        assert_eq!(strongest(&parsed[3..]), None);
+        assert!(!parsed[0].is_stale());
+    }
+
+    #[test]
+    fn answers_basic() {
+        // RFC 7617 section 2
+        let challenge = challenges(&["Basic realm=\"WallyWorld\""]).remove(0);
+        let credentials = Credentials::new("Aladdin", "open sesame");
+        assert_eq!(
+            challenge
+                .authorization("GET", "/", &credentials, 1)
+                .as_deref(),
+            Some("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==")
+        );
+    }
+
+    #[test]
+    fn answers_digest() {
+        // RFC 7616 section 3.9.1
+        let credentials = Credentials::new("Mufasa", "Circle of Life");
+        let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+        for (algorithm, response) in [
+            ("MD5", "8ca523f5e9506fed4657c9700eebdbec"),
+            (
+                "SHA-256",
+                "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1",
+            ),
+        ] {
+            let authorization = digest(algorithm)
+                .answer("GET", "/dir/index.html", &credentials, 1, cnonce)
+                .unwrap();
+            assert_eq!(field(&authorization, "response"), response);
+            assert_eq!(field(&authorization, "username"), "Mufasa");
+            assert_eq!(field(&authorization, "uri"), "/dir/index.html");
+            assert_eq!(field(&authorization, "qop"), "auth");
+            assert_eq!(field(&authorization, "nc"), "00000001");
+            assert_eq!(field(&authorization, "cnonce"), cnonce);
+            assert_eq!(field(&authorization, "algorithm"), algorithm);
+            assert_eq!(
+                field(&authorization, "opaque"),
+                "FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS"
+            );
+        }
+    }
+
+    #[test]
+    fn answers_digest_without_qop() {
+        // RFC 2069, the response leaves out nc, cnonce and qop
+        let challenge = challenges(&[
+            "Digest realm=\"testrealm@host.com\", nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\"",
+        ])
+        .remove(0);
+        let authorization = challenge
+            .answer(
+                "GET",
+                "/dir/index.html",
+                &Credentials::new("Mufasa", "CircleOfLife"),
+                1,
+                "unused",
+            )
+            .unwrap();
+        assert_eq!(
+            field(&authorization, "response"),
+            "1949323746fe6a43ef61f9606e7febea"
+        );
+        assert!(!authorization.contains("cnonce"));
+    }
+
+    #[test]
+    fn sends_credentials_ahead_below_the_directory() {
+        let url = |value: &str| Url::parse(value).unwrap();
+        let challenge = digest("MD5");
+        let mut cache = CredentialCache::new();
+        cache.insert(
+            &url("http://example.org/dir/index.html"),
+            &challenge,
+            Credentials::new("Mufasa", "Circle of Life"),
+        );
+        let realm = "http-auth@example.org";
+        assert!(cache.covers(&url("http://example.org/dir/other"), realm));
+        assert!(!cache.covers(&url("http://example.org/"), realm));
+        assert!(!cache.covers(&url("https://example.org/dir/other"), realm));
+
+        let first = cache
+            .authorization("GET", &url("http://example.org/dir/a?q"))
+            .unwrap();
+        assert_eq!(field(&first, "uri"), "/dir/a?q");
+        assert_eq!(field(&first, "nc"), "00000001");
+        let second = cache
+            .authorization("GET", &url("http://example.org/dir/b"))
+            .unwrap();
+        assert_eq!(field(&second, "nc"), "00000002");
+        assert!(cache
+            .authorization("GET", &url("http://example.org/other"))
+            .is_none());
+
+        cache.remove(&url("http://example.org/"), realm);
+        assert!(cache.get(&url("http://example.org/"), realm).is_none());
+    }
+
+    #[test]
+    fn reads_userinfo() {
+        let credentials = Credentials::from_userinfo("user%40home:pa%3Ass:word").unwrap();
+        assert_eq!(credentials.username.as_str(), "user@home");
+        assert_eq!(credentials.password.as_str(), "pa:ss:word");
+        assert_eq!(
+            Credentials::from_userinfo("user")
+                .unwrap()
+                .password
+                .as_str(),
+            ""
+        );
+        assert!(Credentials::from_userinfo(":password").is_none());
+        assert!(!format!("{credentials:?}").contains("pa:ss"));
+    }
+
+    #[test]
+    fn reads_netrc() {
+        let path = std::env::temp_dir().join(format!("netrc-{}", std::process::id()));
+        fs::write(
+            &path,
+            "machine other.org login nobody password nothing\n\
+             macdef init\nmachine example.org login wrong\n\n\
+             machine Example.org\n  login \"some one\"\n  account x password \"p\\\"w\"\n\
+             default login anonymous password guest\n",
+        )
+        .unwrap();
+        let credentials = netrc(&path, "example.org").unwrap().unwrap();
+        assert_eq!(credentials.username.as_str(), "some one");
+        assert_eq!(credentials.password.as_str(), "p\"w");
+        let credentials = netrc(&path, "elsewhere.org").unwrap().unwrap();
+        assert_eq!(credentials.username.as_str(), "anonymous");
+
+        fs::write(&path, "machine other.org login nobody\n").unwrap();
+        assert!(netrc(&path, "example.org").unwrap().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,259 @@
+use super::{
+    chunked::ChunkedReader,
+    encoding,
+    pool::{Connection, Pool},
+    Error, HeaderMap, Response,
+};
+use crate::url::Url;
+use std::{
+    io::{self, prelude::*, Take},
+    sync::{Arc, Mutex},
+};
+
+// Most of a body that is read past the end of its content to keep the connection, anything
+// longer is cheaper to drop along with the connection
+const DRAIN_BYTES: u64 = 64 * 1024;
+
+// Framing
+// How the end of the body is told apart on the wire
+enum Framing {
+    Length(Take<Connection>),
+    Chunked(ChunkedReader<Connection>),
+    Close(Connection),
+}
+
+// RawBody
+// The body exactly as framed on the wire, the connection is handed back to the pool once the
+// body has been read to the end
+struct RawBody {
+    framing: Option<Framing>,
+    // Where the connection goes afterwards, None when it can't carry another request
+    pool: Option<(Arc<Mutex<Pool>>, Url)>,
+    trailers: Arc<Mutex<HeaderMap>>,
+}
+
+impl RawBody {
+    // drain
+    // Read what a decoder left of the body, e.g. the last chunk and the trailers after the end
+    // of a gzip stream, so the connection can still go back to the pool
+    // ---- a body that runs until the connection closes, goes on past DRAIN_BYTES or turns out
+    //      malformed takes its connection with it instead
+    fn drain(&mut self) {
+        if matches!(self.framing, Some(Framing::Close(_))) {
+            self.framing = None;
+            return;
+        }
+        let drained = io::copy(&mut self.by_ref().take(DRAIN_BYTES), &mut io::sink());
+        if drained.is_err() || self.framing.is_some() {
+            self.framing = None;
+        }
+    }
+
+    fn finish(&mut self) {
+        let connection = match self.framing.take() {
+            Some(Framing::Length(reader)) => reader.into_inner(),
+            Some(Framing::Chunked(reader)) => {
+                let (connection, trailers) = reader.into_parts();
+                *self.trailers.lock().unwrap() = trailers;
+                connection
+            }
+            Some(Framing::Close(_)) | None => return,
+        };
+        if let Some((pool, url)) = self.pool.take() {
+            pool.lock().unwrap().put(&url, connection);
+        }
+    }
+}
+
+impl Read for RawBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = match &mut self.framing {
+            None => return Ok(0),
+            Some(Framing::Length(reader)) => {
+                let read = reader.read(buf)?;
+                if read == 0 && reader.limit() > 0 && !buf.is_empty() {
+                    return Err(io::Error::other(Error::MalformedResponse(
+                        "body is shorter than Content-Length",
+                    )));
+                }
+                read
+            }
+            Some(Framing::Chunked(reader)) => reader.read(buf).map_err(|err| match err.kind() {
+                io::ErrorKind::InvalidData => {
+                    io::Error::other(Error::MalformedResponse("invalid chunked body"))
+                }
+                io::ErrorKind::UnexpectedEof => {
+                    io::Error::other(Error::MalformedResponse("chunked body ended early"))
+                }
+                _ => err,
+            })?,
+            Some(Framing::Close(reader)) => match reader.read(buf) {
+                // Plenty of servers close TLS connections without sending close_notify first
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => 0,
+                result => result?,
+            },
+        };
+        if read == 0 && !buf.is_empty() {
+            self.finish();
+        }
+        Ok(read)
+    }
+}
+
+// SharedBody
+// The raw body as a decoder reads it, Body keeps another handle to drain it once the decoder
+// is done
+#[derive(Clone)]
+struct SharedBody(Arc<Mutex<RawBody>>);
+
+impl Read for SharedBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().read(buf)
+    }
+}
+
+// Body
+// A response body being read off the connection, already decoded unless the request asked to
+// keep the Content-Encoding
+pub struct Body {
+    reader: Box<dyn Read + Send>,
+    // Set while a decoder sits between the reader and the connection
+    raw: Option<SharedBody>,
+    trailers: Arc<Mutex<HeaderMap>>,
+}
+
+impl Body {
+    // new
+    // Work out the framing of the body that follows "response", the body is framed by chunked
+    // transfer coding or Content-Length when there is either, otherwise it runs until the
+    // server closes the connection
+    pub(crate) fn new(
+        response: &mut Response,
+        method: &str,
+        connection: Connection,
+        pool: (Arc<Mutex<Pool>>, Url),
+        max_decoded_size: Option<u64>,
+    ) -> Result<Body, Error> {
+        let has_body = method != "HEAD" && !matches!(response.status, 101 | 204 | 304);
+        let framing = if !has_body {
+            Framing::Length(connection.take(0))
+        } else if response.is_chunked() {
+            Framing::Chunked(ChunkedReader::new(connection))
+        } else if response.header("Transfer-Encoding").is_some() {
+            // Any other transfer coding can only be delimited by closing the connection
+            Framing::Close(connection)
+        } else {
+            match response.content_length()? {
+                Some(length) => Framing::Length(connection.take(length)),
+                None => Framing::Close(connection),
+            }
+        };
+        if matches!(framing, Framing::Close(_)) {
+            response.keep_alive = false;
+        }
+        let empty = matches!(&framing, Framing::Length(reader) if reader.limit() == 0);
+
+        let trailers = Arc::new(Mutex::new(HeaderMap::new()));
+        let mut raw_body = RawBody {
+            framing: Some(framing),
+            pool: response.keep_alive.then_some(pool),
+            trailers: trailers.clone(),
+        };
+        if empty {
+            raw_body.finish();
+        }
+
+        Ok(
+            match (response.header("Content-Encoding"), max_decoded_size) {
+                (Some(content_encoding), Some(limit)) if !empty => {
+                    let raw = SharedBody(Arc::new(Mutex::new(raw_body)));
+                    Body {
+                        reader: encoding::decoder(Box::new(raw.clone()), content_encoding, limit)?,
+                        raw: Some(raw),
+                        trailers,
+                    }
+                }
+                _ => Body {
+                    reader: Box::new(raw_body),
+                    raw: None,
+                    trailers,
+                },
+            },
+        )
+    }
+
+    // A body that was read already, e.g. one kept by the cache
+    pub(crate) fn from_bytes(body: Vec<u8>) -> Body {
+        Body {
+            reader: Box::new(io::Cursor::new(body)),
+            raw: None,
+            trailers: Arc::default(),
+        }
+    }
+
+    // record
+    // Hand a copy of the body to "on_end" once it has been read to the end, a body longer than
+    // "limit" is only passed on
+    pub(crate) fn record(
+        self,
+        limit: usize,
+        on_end: impl FnOnce(Vec<u8>) + Send + 'static,
+    ) -> Body {
+        Body {
+            reader: Box::new(Recorder {
+                reader: self.reader,
+                recorded: Some(vec![]),
+                limit,
+                on_end: Some(on_end),
+            }),
+            raw: self.raw,
+            trailers: self.trailers,
+        }
+    }
+
+    // Fields sent after a chunked body, only known once the body has been read to the end
+    pub fn trailers(&self) -> HeaderMap {
+        self.trailers.lock().unwrap().clone()
+    }
+}
+
+// Recorder
+// Passes a body on while keeping a copy of it, see Body::record
+struct Recorder<F> {
+    reader: Box<dyn Read + Send>,
+    // None once the body went past "limit"
+    recorded: Option<Vec<u8>>,
+    limit: usize,
+    on_end: Option<F>,
+}
+
+impl<F: FnOnce(Vec<u8>)> Read for Recorder<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            if let (Some(recorded), Some(on_end)) = (self.recorded.take(), self.on_end.take()) {
+                on_end(recorded);
+            }
+        } else if let Some(recorded) = &mut self.recorded {
+            if recorded.len() + read > self.limit {
+                self.recorded = None;
+            } else {
+                recorded.extend_from_slice(&buf[..read]);
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        // Decoders stop at the end of their stream without reading the raw body to its end
+        if read == 0 && !buf.is_empty() {
+            if let Some(raw) = self.raw.take() {
+                raw.0.lock().unwrap().drain();
+            }
+        }
+        Ok(read)
+    }
+}
@@ -0,0 +1,855 @@
+use super::{
+    date,
+    header::{CacheControl, HeaderMap},
+    Request, Response, Timings,
+};
+use crate::url::Url;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, prelude::*},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+// Bodies bigger than this aren't kept, they're most likely downloads
+pub(crate) const MAX_ENTRY_BYTES: usize = 8 * 1024 * 1024;
+
+// Bodies kept in memory together, the least recently used go first once there are more
+const MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+// Longest a response without explicit freshness is taken as fresh because of its Last-Modified
+const MAX_HEURISTIC_FRESHNESS: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Status codes that may be stored without saying how long they stay fresh, RFC 9110 section
+// 15.1
+const HEURISTICALLY_CACHEABLE: [u16; 11] = [200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+
+// Entry
+// A stored response, one for every URL and set of request headers the response varies on
+#[derive(Clone, Debug)]
+pub struct Entry {
+    // The URL without its fragment or credentials
+    pub url: String,
+    // Request headers named by Vary and the values they had, None when the request didn't
+    // send them
+    pub vary: Vec<(String, Option<String>)>,
+    pub status: u16,
+    pub reason: String,
+    pub headers: HeaderMap,
+    // Always decoded, Content-Encoding and the framing headers aren't kept
+    pub body: Vec<u8>,
+    // When the request went out and the response came back, RFC 9111 section 4.2.3
+    pub request_time: SystemTime,
+    pub response_time: SystemTime,
+    // Order of the last lookup that found the entry, the lowest gets evicted first
+    last_used: u64,
+    // Whether the request carried credentials
+    authorized: bool,
+}
+
+impl Entry {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    pub fn cache_control(&self) -> CacheControl {
+        self.headers.cache_control()
+    }
+
+    // age
+    // How old the response is by now, RFC 9111 section 4.2.3
+    pub fn age(&self, now: SystemTime) -> Duration {
+        let since = |later: SystemTime, earlier: SystemTime| {
+            later.duration_since(earlier).unwrap_or_default()
+        };
+        let date = self
+            .header("Date")
+            .and_then(date::parse)
+            .unwrap_or(self.response_time);
+        let apparent_age = since(self.response_time, date);
+        let age_value = Duration::from_secs(
+            self.header("Age")
+                .and_then(|age| age.parse().ok())
+                .unwrap_or(0),
+        );
+        let corrected_age_value = age_value + since(self.response_time, self.request_time);
+        apparent_age.max(corrected_age_value) + since(now, self.response_time)
+    }
+
+    // freshness_lifetime
+    // How long the response stays fresh after the server generated it, RFC 9111 section 4.2.1
+    // ---- without max-age or Expires, a tenth of the time since Last-Modified is taken as a
+    //      guess for the statuses that allow it, section 4.2.2
+    pub fn freshness_lifetime(&self) -> Duration {
+        if let Some(max_age) = self.cache_control().max_age {
+            return Duration::from_secs(max_age);
+        }
+        let date = self
+            .header("Date")
+            .and_then(date::parse)
+            .unwrap_or(self.response_time);
+        if let Some(expires) = self.header("Expires") {
+            // An Expires that doesn't parse, such as "0", means already expired
+            return date::parse(expires)
+                .and_then(|expires| expires.duration_since(date).ok())
+                .unwrap_or_default();
+        }
+        match self.header("Last-Modified").and_then(date::parse) {
+            Some(last_modified) if HEURISTICALLY_CACHEABLE.contains(&self.status) => date
+                .duration_since(last_modified)
+                .map_or(Duration::ZERO, |since| {
+                    (since / 10).min(MAX_HEURISTIC_FRESHNESS)
+                }),
+            _ => Duration::ZERO,
+        }
+    }
+
+    // Whether the response can be used without asking the server first
+    pub fn is_fresh(&self, now: SystemTime) -> bool {
+        !self.cache_control().no_cache && self.age(now) < self.freshness_lifetime()
+    }
+
+    // Whether the server can be asked if the response is still good
+    pub fn has_validator(&self) -> bool {
+        self.header("ETag").is_some() || self.header("Last-Modified").is_some()
+    }
+
+    // The response as received, with an Age header saying how long it has been stored
+    pub fn response(&self, now: SystemTime) -> Response {
+        let mut headers = self.headers.clone();
+        headers.insert("Age", &self.age(now).as_secs().to_string());
+        Response {
+            status: self.status,
+            reason: self.reason.clone(),
+            headers,
+            body: vec![],
+            trailers: HeaderMap::new(),
+            keep_alive: true,
+            redirects: vec![],
+            status_line: format!("HTTP/1.1 {} {}", self.status, self.reason),
+            request_head: String::new(),
+            cached: true,
+            timings: Timings::default(),
+        }
+    }
+
+    // The request headers the response varies on have to be the same, RFC 9111 section 4.1
+    fn matches(&self, request: &Request) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| request_header(request, name).as_deref() == value.as_deref())
+    }
+
+    // on_disk
+    // Whether the response may go in the directory, which is kept like a shared cache would
+    // ---- private responses and answers to requests with credentials stay in memory, RFC 9111
+    //      section 3.5, unless the server said shared caches can keep them
+    // ---- cookies the server set don't belong in the directory either
+    fn on_disk(&self) -> bool {
+        let cache_control = self.cache_control();
+        let shareable =
+            cache_control.public || cache_control.s_maxage || cache_control.must_revalidate;
+        !cache_control.private
+            && (!self.authorized || shareable)
+            && !self.headers.contains("Set-Cookie")
+    }
+
+    // write
+    // Serialize the entry for the disk cache, a header section that reads like an HTTP message
+    // with the body after it
+    fn write(&self, out: &mut Vec<u8>) {
+        let seconds = |time: SystemTime| {
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs())
+        };
+        out.extend_from_slice(
+            format!(
+                "{}\r\n{} {} {}\r\n",
+                self.url,
+                seconds(self.request_time),
+                seconds(self.response_time),
+                self.body.len()
+            )
+            .as_bytes(),
+        );
+        for (name, value) in &self.vary {
+            match value {
+                Some(value) => out.extend_from_slice(format!("> {name}: {value}\r\n").as_bytes()),
+                None => out.extend_from_slice(format!("> {name}\r\n").as_bytes()),
+            }
+        }
+        out.extend_from_slice(format!("HTTP/1.1 {} {}\r\n", self.status, self.reason).as_bytes());
+        for (name, value) in &self.headers {
+            out.extend_from_slice(format!("{name}: {value}\r\n").as_bytes());
+        }
+        out.extend_from_slice(b"\r\n");
+        out.extend_from_slice(&self.body);
+    }
+
+    // read
+    // The next entry serialized by write, None at the end of "input"
+    fn read(input: &mut &[u8]) -> io::Result<Option<Entry>> {
+        if input.is_empty() {
+            return Ok(None);
+        }
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a cache entry");
+        let mut next_line = || -> io::Result<String> {
+            let mut line = vec![];
+            input.read_until(b'\n', &mut line)?;
+            if !line.ends_with(b"\r\n") {
+                return Err(invalid());
+            }
+            line.truncate(line.len() - 2);
+            String::from_utf8(line).map_err(|_| invalid())
+        };
+
+        let url = next_line()?;
+        let times = next_line()?;
+        let [request_time, response_time, body_len] = times
+            .split(' ')
+            .map(|field| field.parse::<u64>().map_err(|_| invalid()))
+            .collect::<io::Result<Vec<u64>>>()?[..]
+        else {
+            return Err(invalid());
+        };
+        let mut vary = vec![];
+        let mut status_line = next_line()?;
+        while let Some(header) = status_line.strip_prefix("> ") {
+            vary.push(match header.split_once(": ") {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (header.to_string(), None),
+            });
+            status_line = next_line()?;
+        }
+        let mut status_line = status_line.splitn(3, ' ').skip(1);
+        let status = status_line
+            .next()
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(invalid)?;
+        let reason = status_line.next().unwrap_or_default().to_string();
+        let mut headers = HeaderMap::new();
+        loop {
+            let line = next_line()?;
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line.split_once(": ").ok_or_else(invalid)?;
+            headers.append(name, value);
+        }
+
+        let body_len = body_len as usize;
+        if input.len() < body_len {
+            return Err(invalid());
+        }
+        let (body, rest) = input.split_at(body_len);
+        *input = rest;
+        let time = |seconds: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(seconds);
+        Ok(Some(Entry {
+            url,
+            vary,
+            status,
+            reason,
+            headers,
+            body: body.to_vec(),
+            request_time: time(request_time),
+            response_time: time(response_time),
+            last_used: 0,
+            // It wouldn't be on disk otherwise
+            authorized: false,
+        }))
+    }
+}
+
+// Cache
+// Responses to GET requests kept for reuse, RFC 9111, shared by all clones of a Client
+// ---- entries live in memory, and also in a directory when one is set, which outlives the
+//      session
+// ---- a URL's entries are read from the directory the first time the URL is looked up
+#[derive(Debug, Default)]
+pub struct Cache {
+    entries: HashMap<String, Vec<Entry>>,
+    dir: Option<PathBuf>,
+    memory_bytes: usize,
+    uses: u64,
+}
+
+impl Cache {
+    pub fn new() -> Self {
+        Cache::default()
+    }
+
+    // Keep the responses in "dir" as well from now on, None keeps them in memory only
+    pub fn set_dir(&mut self, dir: Option<PathBuf>) -> io::Result<()> {
+        if let Some(dir) = &dir {
+            fs::create_dir_all(dir)?;
+        }
+        self.dir = dir;
+        Ok(())
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    // lookup
+    // The stored response "request" would get, fresh or not
+    pub fn lookup(&mut self, request: &Request) -> Option<Entry> {
+        let key = key(&request.url);
+        self.load(&key);
+        self.uses += 1;
+        let uses = self.uses;
+        let entry = self
+            .entries
+            .get_mut(&key)?
+            .iter_mut()
+            .find(|entry| entry.matches(request))?;
+        entry.last_used = uses;
+        Some(entry.clone())
+    }
+
+    // store
+    // Keep the response to "request" along with its body, when RFC 9111 section 3 allows it
+    // and there is a point to it, a response that can't be reused nor revalidated is left out
+    pub fn store(
+        &mut self,
+        request: &Request,
+        response: &Response,
+        body: Vec<u8>,
+        request_time: SystemTime,
+        response_time: SystemTime,
+    ) {
+        if !is_storable(request, response) || body.len() > MAX_ENTRY_BYTES {
+            return;
+        }
+        let vary = match vary(response) {
+            Some(names) => names
+                .into_iter()
+                .map(|name| {
+                    let value = request_header(request, &name);
+                    (name, value)
+                })
+                .collect(),
+            None => return,
+        };
+        let mut headers = response.headers.clone();
+        headers.retain(|name, _| !is_framing(name));
+        headers.append("Content-Length", &body.len().to_string());
+        self.uses += 1;
+        let entry = Entry {
+            url: key(&request.url),
+            vary,
+            status: response.status,
+            reason: response.reason.clone(),
+            headers,
+            body,
+            request_time,
+            response_time,
+            last_used: self.uses,
+            authorized: request.has_header("Authorization"),
+        };
+
+        let key = entry.url.clone();
+        self.load(&key);
+        let entries = self.entries.entry(key.clone()).or_default();
+        if let Some(index) = entries.iter().position(|stored| stored.vary == entry.vary) {
+            self.memory_bytes -= entries.remove(index).body.len();
+        }
+        self.memory_bytes += entry.body.len();
+        entries.push(entry);
+        self.save(&key);
+        self.evict();
+    }
+
+    // freshen
+    // Update the stored response "request" revalidated with the headers of the 304 answering
+    // it, RFC 9111 section 4.3.4, and hand it back
+    pub fn freshen(
+        &mut self,
+        request: &Request,
+        not_modified: &Response,
+        request_time: SystemTime,
+        response_time: SystemTime,
+    ) -> Option<Entry> {
+        let key = key(&request.url);
+        let entry = self
+            .entries
+            .get_mut(&key)?
+            .iter_mut()
+            .find(|entry| entry.matches(request))?;
+        // A 304 for another version of the resource doesn't make this one any fresher
+        let etag = not_modified.header("ETag");
+        if etag.is_some() && etag != entry.header("ETag") {
+            return None;
+        }
+        // Each field the 304 carries replaces all of the stored one, repeated fields included
+        let mut replaced: Vec<&str> = vec![];
+        for (name, value) in not_modified
+            .headers
+            .iter()
+            .filter(|(name, _)| !is_framing(name))
+        {
+            if !replaced.iter().any(|done| done.eq_ignore_ascii_case(name)) {
+                entry.headers.remove(name);
+                replaced.push(name);
+            }
+            entry.headers.append(name, value);
+        }
+        entry.request_time = request_time;
+        entry.response_time = response_time;
+        entry.authorized |= request.has_header("Authorization");
+        let entry = entry.clone();
+        self.save(&key);
+        Some(entry)
+    }
+
+    // invalidate
+    // Forget the responses for "url", after a request that may have changed it went through,
+    // RFC 9111 section 4.4
+    pub fn invalidate(&mut self, url: &Url) {
+        let key = key(url);
+        if let Some(entries) = self.entries.remove(&key) {
+            self.memory_bytes -= entries.iter().map(|entry| entry.body.len()).sum::<usize>();
+        }
+        if let Some(path) = self.path(&key) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    // Every stored response for "url"
+    pub fn get(&mut self, url: &Url) -> Vec<Entry> {
+        let key = key(url);
+        self.load(&key);
+        self.entries.get(&key).cloned().unwrap_or_default()
+    }
+
+    // entries
+    // Every stored response, the ones only on disk included, ordered by URL
+    pub fn entries(&mut self) -> Vec<Entry> {
+        if let Some(dir) = self.dir.clone() {
+            for file in fs::read_dir(dir).into_iter().flatten().flatten() {
+                if let Some(entries) = read_file(&file.path()) {
+                    if let Some(entry) = entries.first() {
+                        let key = entry.url.clone();
+                        self.load(&key);
+                    }
+                }
+            }
+        }
+        let mut entries: Vec<Entry> = self.entries.values().flatten().cloned().collect();
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+        entries
+    }
+
+    // clear
+    // Forget every response, the ones on disk too
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.entries.clear();
+        self.memory_bytes = 0;
+        if let Some(dir) = &self.dir {
+            for file in fs::read_dir(dir)? {
+                let path = file?.path();
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == "cache")
+                {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Where the entries of "key" go on disk, named after its SHA-256
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        let digest: String = Sha256::digest(key.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        Some(self.dir.as_ref()?.join(format!("{digest}.cache")))
+    }
+
+    // Bring the entries of "key" into memory, unless they already are
+    fn load(&mut self, key: &str) {
+        if self.entries.contains_key(key) {
+            return;
+        }
+        let Some(entries) = self.path(key).and_then(|path| read_file(&path)) else {
+            return;
+        };
+        let entries: Vec<Entry> = entries
+            .into_iter()
+            .filter(|entry| entry.url == key)
+            .collect();
+        if !entries.is_empty() {
+            self.memory_bytes += entries.iter().map(|entry| entry.body.len()).sum::<usize>();
+            self.entries.insert(key.to_string(), entries);
+        }
+    }
+
+    // Write the entries of "key" that may go on disk, a failed write only costs the disk copy
+    fn save(&self, key: &str) {
+        let Some(path) = self.path(key) else { return };
+        let mut file = vec![];
+        for entry in self
+            .entries
+            .get(key)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.on_disk())
+        {
+            entry.write(&mut file);
+        }
+        let _ = if file.is_empty() {
+            fs::remove_file(path)
+        } else {
+            fs::write(path, file)
+        };
+    }
+
+    // Drop the least recently used entries from memory until the rest fit, the disk keeps its
+    // copies
+    fn evict(&mut self) {
+        while self.memory_bytes > MAX_MEMORY_BYTES {
+            let Some((key, index, _)) = self
+                .entries
+                .iter()
+                .flat_map(|(key, entries)| {
+                    entries
+                        .iter()
+                        .enumerate()
+                        .map(move |(index, entry)| (key, index, entry.last_used))
+                })
+                .min_by_key(|(_, _, last_used)| *last_used)
+                .map(|(key, index, last_used)| (key.clone(), index, last_used))
+            else {
+                return;
+            };
+            let entries = self.entries.get_mut(&key).unwrap();
+            self.memory_bytes -= entries.remove(index).body.len();
+            if entries.is_empty() {
+                self.entries.remove(&key);
+            }
+        }
+    }
+}
+
+// key
+// URLs are stored without credentials or fragment, neither goes to the server
+pub fn key(url: &Url) -> String {
+    format!(
+        "{}://{}{}",
+        url.scheme,
+        url.host_header(),
+        url.request_target()
+    )
+}
+
+// is_cacheable
+// Whether "request" may be answered from the cache and its response stored, only GETs for the
+// whole, decoded body are
+pub fn is_cacheable(request: &Request) -> bool {
+    request.method == "GET"
+        && request.decode_content
+        && !request.has_header("Range")
+        && !request.headers.cache_control().no_store
+}
+
+// is_storable
+// RFC 9111 section 3, a response also has to say how long it stays fresh or how to check on
+// it, otherwise storing it is pointless
+pub fn is_storable(request: &Request, response: &Response) -> bool {
+    let cache_control = response.headers.cache_control();
+    let explicit = cache_control.max_age.is_some() || response.header("Expires").is_some();
+    let validator = response.header("ETag").is_some() || response.header("Last-Modified").is_some();
+    is_cacheable(request)
+        && !cache_control.no_store
+        && !matches!(response.status, 206 | 304)
+        && (explicit || (validator && HEURISTICALLY_CACHEABLE.contains(&response.status)))
+}
+
+// Field names of the Vary headers, None for "*" which can't ever be matched
+fn vary(response: &Response) -> Option<Vec<String>> {
+    let mut names = vec![];
+    for name in response.headers.list("Vary") {
+        if name == "*" {
+            return None;
+        }
+        names.push(name.to_string());
+    }
+    Some(names)
+}
+
+// Value a request header had on the wire, repeated ones joined, Accept-Encoding is added when
+// the request goes out unless set by the caller
+fn request_header(request: &Request, name: &str) -> Option<String> {
+    request.headers.combined(name).or_else(|| {
+        name.eq_ignore_ascii_case("Accept-Encoding")
+            .then(|| super::encoding::ACCEPT_ENCODING.to_string())
+    })
+}
+
+// Headers about how the body came over the wire rather than what it is, RFC 9111 section 3.1
+fn is_framing(name: &str) -> bool {
+    [
+        "Content-Encoding",
+        "Content-Length",
+        "Transfer-Encoding",
+        "Connection",
+        "Keep-Alive",
+    ]
+    .iter()
+    .any(|framing| framing.eq_ignore_ascii_case(name))
+}
+
+fn read_file(path: &Path) -> Option<Vec<Entry>> {
+    let file = fs::read(path).ok()?;
+    let mut input = file.as_slice();
+    let mut entries = vec![];
+    // A file that got cut short keeps the entries before the damage
+    while let Ok(Some(entry)) = Entry::read(&mut input) {
+        entries.push(entry);
+    }
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        let mut request = Request::get(Url::parse("http://example.com/page?q=1#top").unwrap());
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        request
+    }
+
+    fn response(head: &str) -> Response {
+        let head = format!("{head}\r\n\r\n").replace('\n', "\r\n");
+        Response::read_head(&mut head.as_bytes()).unwrap()
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    // A cache keeping its entries in a fresh directory
+    fn on_disk(name: &str) -> (Cache, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("cache-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut cache = Cache::new();
+        cache.set_dir(Some(dir.clone())).unwrap();
+        (cache, dir)
+    }
+
+    fn reopened(dir: &Path) -> Cache {
+        let mut cache = Cache::new();
+        cache.set_dir(Some(dir.to_path_buf())).unwrap();
+        cache
+    }
+
+    #[test]
+    fn reads_back_what_it_wrote() {
+        let (mut cache, dir) = on_disk("roundtrip");
+        let request = request(&[("Accept-Language", "en")]);
+        let response = response(
+            "HTTP/1.1 200 Fine Thanks\n\
+             Cache-Control: max-age=60\n\
+             Vary: Accept-Language, Accept-Encoding, X-Missing\n\
+             Link: <a>\n\
+             Link: <b>\n\
+             Content-Encoding: gzip\n\
+             Content-Length: 3",
+        );
+        cache.store(
+            &request,
+            &response,
+            b"a\r\n\r\nb".to_vec(),
+            at(100),
+            at(101),
+        );
+
+        let entries = reopened(&dir).get(&request.url);
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.url, "http://example.com/page?q=1");
+        assert_eq!(
+            entry.vary,
+            [
+                (String::from("Accept-Language"), Some(String::from("en"))),
+                (
+                    String::from("Accept-Encoding"),
+                    Some(crate::http::encoding::ACCEPT_ENCODING.to_string())
+                ),
+                (String::from("X-Missing"), None),
+            ]
+        );
+        assert_eq!((entry.status, entry.reason.as_str()), (200, "Fine Thanks"));
+        assert_eq!(
+            entry.headers.get_all("Link").collect::<Vec<_>>(),
+            ["<a>", "<b>"]
+        );
+        assert_eq!(entry.header("Content-Encoding"), None);
+        assert_eq!(entry.header("Content-Length"), Some("6"));
+        assert_eq!(entry.body, b"a\r\n\r\nb");
+        assert_eq!(
+            (entry.request_time, entry.response_time),
+            (at(100), at(101))
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_a_damaged_file_up_to_the_damage() {
+        let (mut cache, dir) = on_disk("damaged");
+        let first = request(&[("Accept-Language", "en")]);
+        let second = request(&[("Accept-Language", "de")]);
+        let response =
+            response("HTTP/1.1 200 OK\nCache-Control: max-age=60\nVary: Accept-Language");
+        cache.store(&first, &response, b"english".to_vec(), at(1), at(1));
+        cache.store(&second, &response, b"deutsch".to_vec(), at(1), at(1));
+
+        let path = cache.path(&key(&first.url)).unwrap();
+        let file = fs::read(&path).unwrap();
+        fs::write(&path, &file[..file.len() - 3]).unwrap();
+        let mut cache = reopened(&dir);
+        assert_eq!(cache.lookup(&first).unwrap().body, b"english");
+        assert!(cache.lookup(&second).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_credentials_and_cookies_off_disk() {
+        let cases = [
+            (
+                &[("Authorization", "Basic eDp5")][..],
+                "Cache-Control: max-age=60",
+                false,
+            ),
+            (
+                &[("Authorization", "Basic eDp5")],
+                "Cache-Control: max-age=60, public",
+                true,
+            ),
+            (
+                &[("Authorization", "Basic eDp5")],
+                "Cache-Control: s-maxage=60, max-age=60",
+                true,
+            ),
+            (
+                &[("Authorization", "Basic eDp5")],
+                "Cache-Control: must-revalidate\nETag: \"1\"",
+                true,
+            ),
+            (&[], "Cache-Control: max-age=60\nSet-Cookie: id=1", false),
+            (&[], "Cache-Control: max-age=60, private", false),
+            (&[], "Cache-Control: max-age=60", true),
+        ];
+        for (index, (headers, head, persisted)) in cases.into_iter().enumerate() {
+            let (mut cache, dir) = on_disk(&format!("shared-{index}"));
+            let request = request(headers);
+            let response = response(&format!("HTTP/1.1 200 OK\n{head}"));
+            cache.store(&request, &response, b"body".to_vec(), at(1), at(1));
+            assert!(cache.lookup(&request).is_some(), "{head}");
+            assert_eq!(
+                reopened(&dir).lookup(&request).is_some(),
+                persisted,
+                "{head}"
+            );
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn freshen_replaces_every_repeated_field() {
+        let mut cache = Cache::new();
+        let request = request(&[]);
+        let stored = response(
+            "HTTP/1.1 200 OK\nETag: \"v1\"\nLink: <a>\nLink: <b>\nX-Kept: 1\nCache-Control: no-cache",
+        );
+        cache.store(&request, &stored, b"body".to_vec(), at(1), at(1));
+
+        let not_modified = response(
+            "HTTP/1.1 304 Not Modified\nETag: \"v1\"\nlink: <c>\nLink: <d>\nCache-Control: max-age=60",
+        );
+        let entry = cache
+            .freshen(&request, &not_modified, at(5), at(6))
+            .unwrap();
+        assert_eq!(
+            entry.headers.get_all("Link").collect::<Vec<_>>(),
+            ["<c>", "<d>"]
+        );
+        assert_eq!(entry.header("X-Kept"), Some("1"));
+        assert_eq!(entry.cache_control().max_age, Some(60));
+        assert!(!entry.cache_control().no_cache);
+        assert_eq!(entry.response_time, at(6));
+        assert_eq!(cache.lookup(&request).unwrap().headers, entry.headers);
+
+        let other_version = response("HTTP/1.1 304 Not Modified\nETag: \"v2\"");
+        assert!(cache
+            .freshen(&request, &other_version, at(7), at(8))
+            .is_none());
+    }
+
+    #[test]
+    fn works_out_freshness() {
+        let entry = |head: &str| {
+            let response = response(&format!("HTTP/1.1 200 OK\n{head}"));
+            Entry {
+                url: String::new(),
+                vary: vec![],
+                status: response.status,
+                reason: response.reason,
+                headers: response.headers,
+                body: vec![],
+                request_time: at(1_000),
+                response_time: at(1_002),
+                last_used: 0,
+                authorized: false,
+            }
+        };
+        let date = "Date: Thu, 01 Jan 1970 00:16:40 GMT";
+        let fresh = entry(&format!("{date}\nCache-Control: max-age=100\nAge: 10"));
+        assert_eq!(fresh.age(at(1_002)), Duration::from_secs(12));
+        assert!(fresh.is_fresh(at(1_080)));
+        assert!(!fresh.is_fresh(at(1_090)));
+
+        let expires = entry(&format!("{date}\nExpires: Thu, 01 Jan 1970 00:17:40 GMT"));
+        assert_eq!(expires.freshness_lifetime(), Duration::from_secs(60));
+        assert_eq!(entry("Expires: 0").freshness_lifetime(), Duration::ZERO);
+        let heuristic = entry(&format!(
+            "{date}\nLast-Modified: Thu, 01 Jan 1970 00:00:00 GMT"
+        ));
+        assert_eq!(heuristic.freshness_lifetime(), Duration::from_secs(100));
+        assert!(!entry("Cache-Control: max-age=100, no-cache").is_fresh(at(1_002)));
+    }
+
+    #[test]
+    fn stores_only_what_can_be_reused() {
+        let get = request(&[]);
+        let storable = |request: &Request, head: &str| {
+            is_storable(request, &response(&format!("HTTP/1.1 {head}")))
+        };
+        assert!(storable(&get, "200 OK\nCache-Control: max-age=1"));
+        assert!(storable(
+            &get,
+            "404 Not Found\nLast-Modified: Thu, 01 Jan 1970 00:00:00 GMT"
+        ));
+        assert!(!storable(&get, "200 OK"));
+        assert!(!storable(&get, "302 Found\nETag: \"1\""));
+        assert!(!storable(
+            &get,
+            "206 Partial Content\nCache-Control: max-age=1"
+        ));
+        assert!(!storable(
+            &get,
+            "200 OK\nCache-Control: max-age=1, no-store"
+        ));
+        let range = request(&[("Range", "bytes=0-1")]);
+        assert!(!storable(&range, "200 OK\nCache-Control: max-age=1"));
+        let no_store = request(&[("Cache-Control", "no-store")]);
+        assert!(!storable(&no_store, "200 OK\nCache-Control: max-age=1"));
+    }
+}
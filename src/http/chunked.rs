@@ -0,0 +1,185 @@
+use super::HeaderMap;
+use std::io::{self, prelude::*};
+
+// Longest chunk-size line or trailer field accepted, extensions included
+const MAX_LINE_BYTES: u64 = 8 * 1024;
+
+// ChunkedReader
+// Decode a "Transfer-Encoding: chunked" body as it's read, the trailer fields that follow the
+// last chunk are available once the reader is exhausted
+pub struct ChunkedReader<R> {
+    inner: R,
+    // Bytes left in the current chunk
+    remaining: u64,
+    // A chunk has been read completely, the CRLF after its data is still pending
+    chunk_end: bool,
+    done: bool,
+    trailers: HeaderMap,
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    pub fn new(inner: R) -> Self {
+        ChunkedReader {
+            inner,
+            remaining: 0,
+            chunk_end: false,
+            done: false,
+            trailers: HeaderMap::new(),
+        }
+    }
+
+    pub fn trailers(&self) -> &HeaderMap {
+        &self.trailers
+    }
+
+    pub fn into_parts(self) -> (R, HeaderMap) {
+        (self.inner, self.trailers)
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = vec![];
+        self.inner
+            .by_ref()
+            .take(MAX_LINE_BYTES)
+            .read_until(b'\n', &mut line)?;
+        if !line.ends_with(b"\n") {
+            return Err(if line.len() as u64 == MAX_LINE_BYTES {
+                invalid_data("chunk line is too long")
+            } else {
+                io::ErrorKind::UnexpectedEof.into()
+            });
+        }
+        Ok(String::from_utf8_lossy(&line)
+            .trim_end_matches(['\r', '\n'])
+            .to_string())
+    }
+
+    fn next_chunk(&mut self) -> io::Result<()> {
+        if self.chunk_end {
+            if !self.read_line()?.is_empty() {
+                return Err(invalid_data("missing CRLF after chunk data"));
+            }
+            self.chunk_end = false;
+        }
+
+        // Chunk extensions after ';' carry nothing this client understands, the size itself is
+        // 1*HEXDIG (RFC 9112 section 7.1), a sign from_str_radix would let through could frame the
+        // body differently than another hop does
+        let line = self.read_line()?;
+        let size = line.split(';').next().unwrap_or_default().trim_end();
+        if size.is_empty() || !size.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(invalid_data("invalid chunk size"));
+        }
+        self.remaining =
+            u64::from_str_radix(size, 16).map_err(|_| invalid_data("invalid chunk size"))?;
+
+        if self.remaining == 0 {
+            // Trailer Section
+            loop {
+                let line = self.read_line()?;
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line
+                    .split_once(':')
+                    .ok_or_else(|| invalid_data("trailer line without a colon"))?;
+                self.trailers.append(name.trim(), value.trim());
+            }
+            self.done = true;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.remaining == 0 {
+            if self.done {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+
+        let max = buf.len().min(self.remaining as usize);
+        let read = self.inner.read(&mut buf[..max])?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= read as u64;
+        self.chunk_end = self.remaining == 0;
+        Ok(read)
+    }
+}
+
+fn invalid_data(reason: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(body: &[u8]) -> io::Result<(Vec<u8>, HeaderMap)> {
+        let mut reader = ChunkedReader::new(body);
+        let mut decoded = vec![];
+        reader.read_to_end(&mut decoded)?;
+        let (_, trailers) = reader.into_parts();
+        Ok((decoded, trailers))
+    }
+
+    #[test]
+    fn joins_chunks() {
+        let (body, trailers) = decode(b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n").unwrap();
+        assert_eq!(body, b"hello world");
+        assert!(trailers.is_empty());
+    }
+
+    #[test]
+    fn ignores_extensions_and_reads_trailers() {
+        let input = b"A;name=value\r\n0123456789\r\n0\r\nExpires: never\r\nX-Sum: 1\r\n\r\n";
+        let (body, trailers) = decode(input).unwrap();
+        assert_eq!(body, b"0123456789");
+        assert_eq!(trailers.get("expires"), Some("never"));
+        assert_eq!(trailers.get("X-Sum"), Some("1"));
+    }
+
+    #[test]
+    fn leaves_what_follows_the_body() {
+        let mut input: &[u8] = b"3\r\nabc\r\n0\r\n\r\nHTTP/1.1 200 OK";
+        let mut reader = ChunkedReader::new(&mut input);
+        let mut body = vec![];
+        reader.read_to_end(&mut body).unwrap();
+        assert_eq!(body, b"abc");
+        assert_eq!(input, b"HTTP/1.1 200 OK");
+    }
+
+    #[test]
+    fn rejects_sizes_that_arent_hex_digits() {
+        for input in [
+            &b"+5\r\nhello\r\n0\r\n\r\n"[..],
+            b"-0\r\n\r\n",
+            b" 5\r\nhello\r\n0\r\n\r\n",
+            b"0x5\r\nhello\r\n0\r\n\r\n",
+            b"\r\nhello\r\n0\r\n\r\n",
+            b"10000000000000000\r\n",
+        ] {
+            let err = decode(input).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_missing_crlf_after_data() {
+        let err = decode(b"3\r\nabcX\r\n0\r\n\r\n").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_body_is_unexpected_eof() {
+        let err = decode(b"5\r\nhel").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,431 @@
+use super::{
+    auth::CredentialCache,
+    cache::{self, MAX_ENTRY_BYTES},
+    encoding::MAX_DECODED_BYTES,
+    pool::{Connection, Pool},
+    stream::{self, Socket, Stream},
+    timeout::{CancelToken, Deadline, Timeouts},
+    tls, Body, Cache, CookieJar, Error, ProxyConfig, ProxyKind, Request, Resolver, Response,
+    Timings,
+};
+use crate::url::Url;
+use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, StreamOwned};
+use std::{
+    io::{self, prelude::*, BufReader},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Instant, SystemTime},
+};
+
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+// Client
+// Clones share the same connection pool, cookie jar, credentials and cache, so a clone can be
+// handed to another thread
+#[derive(Clone, Debug)]
+pub struct Client {
+    ca_bundle: Option<PathBuf>,
+    // Built on the first https:// request, system roots can take a while to load
+    tls_config: Option<Arc<ClientConfig>>,
+    pool: Arc<Mutex<Pool>>,
+    // Direct connections unless set, see set_proxy
+    proxy: ProxyConfig,
+    // Host names pinned to addresses, see set_resolver
+    resolver: Resolver,
+    // Set-Cookie of every response is stored here and sent back with every request
+    pub cookies: Arc<Mutex<CookieJar>>,
+    // Credentials given for each realm, sent along before the server asks for them again
+    pub credentials: Arc<Mutex<CredentialCache>>,
+    // Responses to GET requests, reused while fresh and revalidated once stale
+    pub cache: Arc<Mutex<Cache>>,
+    pub max_decoded_size: u64,
+    // Redirects followed per request before giving up, 0 hands every 3xx back to the caller
+    pub max_redirects: usize,
+    pub timeouts: Timeouts,
+    // Cancelling it makes the requests in flight fail with Error::Cancelled, including the body
+    // still being read
+    pub cancel: CancelToken,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client {
+            ca_bundle: None,
+            tls_config: None,
+            pool: Arc::new(Mutex::new(Pool::default())),
+            proxy: ProxyConfig::default(),
+            resolver: Resolver::default(),
+            cookies: Arc::new(Mutex::new(CookieJar::new())),
+            credentials: Arc::new(Mutex::new(CredentialCache::new())),
+            cache: Arc::new(Mutex::new(Cache::new())),
+            max_decoded_size: MAX_DECODED_BYTES,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            timeouts: Timeouts::default(),
+            cancel: CancelToken::default(),
+        }
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Client::default()
+    }
+
+    // Trust the certificates of a PEM bundle on top of the system roots, None goes back to the
+    // system roots only
+    pub fn set_ca_bundle(&mut self, ca_bundle: Option<PathBuf>) -> Result<(), Error> {
+        self.tls_config = Some(tls::client_config(ca_bundle.as_deref())?);
+        self.ca_bundle = ca_bundle;
+        // Connections made under the previous roots shouldn't outlive them
+        self.pool.lock().unwrap().clear();
+        Ok(())
+    }
+
+    pub fn ca_bundle(&self) -> Option<&PathBuf> {
+        self.ca_bundle.as_ref()
+    }
+
+    // Send every request through the proxies of "proxy" from now on
+    pub fn set_proxy(&mut self, proxy: ProxyConfig) {
+        self.proxy = proxy;
+        // Idle connections lead to wherever the previous settings pointed
+        self.pool.lock().unwrap().clear();
+    }
+
+    pub fn proxy(&self) -> &ProxyConfig {
+        &self.proxy
+    }
+
+    // Look host names up through "resolver" from now on, its overrides come before DNS
+    pub fn set_resolver(&mut self, resolver: Resolver) {
+        self.resolver = resolver;
+        // Idle connections may lead to addresses that were pinned differently
+        self.pool.lock().unwrap().clear();
+    }
+
+    pub fn resolver(&self) -> &Resolver {
+        &self.resolver
+    }
+
+    // send
+    // Send the request and read the whole response, see send_streaming
+    pub fn send(&mut self, request: &Request) -> Result<Response, Error> {
+        let (mut response, mut body) = self.send_streaming(request)?;
+        body.read_to_end(&mut response.body)?;
+        response.trailers = body.trailers();
+        Ok(response)
+    }
+
+    // send_streaming
+    // Send the request and follow any Location redirects, every URL visited along the way ends
+    // up in the response's redirect chain, the final body is left for the caller to read
+    pub fn send_streaming(&mut self, request: &Request) -> Result<(Response, Body), Error> {
+        let mut request = request.clone();
+        let mut visited = vec![(request.method.clone(), request.url.clone())];
+        let mut redirects = vec![];
+        let total = Deadline::after(self.timeouts.total, "the request");
+        loop {
+            if self.cancel.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            let (response, mut body) = self.send_once(&request, total)?;
+            let location = match response.header("Location") {
+                Some(location) if is_redirect(response.status) && self.max_redirects > 0 => {
+                    location
+                }
+                _ => {
+                    let mut response = response;
+                    response.redirects = redirects;
+                    return Ok((response, body));
+                }
+            };
+            // Reading the redirect's body to the end frees the connection for the next hop,
+            // a broken one only costs a new connection
+            let _ = io::copy(&mut body, &mut io::sink());
+            if redirects.len() == self.max_redirects {
+                return Err(Error::TooManyRedirects(self.max_redirects));
+            }
+
+            let next_url = request.url.join(location)?;
+            // Credentials are only meant for the origin that asked for them, and so are cookies
+            // the caller set, the jar picks the ones of the next hop
+            if next_url.scheme != request.url.scheme
+                || next_url.host != request.url.host
+                || next_url.port != request.url.port
+            {
+                for name in ["Authorization", "Proxy-Authorization", "Cookie"] {
+                    request.headers.remove(name);
+                }
+            }
+            // 303 always switches to GET, and browsers do the same for a POST answered with
+            // 301/302, only 307 and 308 guarantee the method and body are kept
+            let to_get = match response.status {
+                303 => request.method != "HEAD",
+                301 | 302 => request.method == "POST",
+                _ => false,
+            };
+            if to_get {
+                request.method = String::from("GET");
+                request.body = vec![];
+                request.headers.remove("Content-Type");
+                request.headers.remove("Content-Length");
+            }
+            request.url = next_url;
+
+            if visited.contains(&(request.method.clone(), request.url.clone())) {
+                return Err(Error::RedirectLoop(Box::new(request.url)));
+            }
+            visited.push((request.method.clone(), request.url.clone()));
+            redirects.push(request.url.clone());
+        }
+    }
+
+    fn send_once(
+        &mut self,
+        request: &Request,
+        total: Option<Deadline>,
+    ) -> Result<(Response, Body), Error> {
+        // Cookies are picked per hop, a redirect may lead to a different host or path, a Cookie
+        // header set by the caller wins over the jar
+        let with_cookies;
+        let request = match self.cookies.lock().unwrap().header(&request.url) {
+            Some(cookies) if !request.has_header("Cookie") => {
+                with_cookies = request.clone().header("Cookie", &cookies);
+                &with_cookies
+            }
+            _ => request,
+        };
+        // Same for credentials of a realm the request falls under, an Authorization header set
+        // by the caller wins over the cache
+        let with_credentials;
+        let authorization = match request.has_header("Authorization") {
+            true => None,
+            false => self
+                .credentials
+                .lock()
+                .unwrap()
+                .authorization(&request.method, &request.url),
+        };
+        let request = match authorization {
+            Some(authorization) => {
+                with_credentials = request.clone().header("Authorization", &authorization);
+                &with_credentials
+            }
+            None => request,
+        };
+        self.through_cache(request, total)
+    }
+
+    // through_cache
+    // Answer from the cache while the stored response is fresh, ask the server whether it still
+    // holds once it isn't, and keep the response for next time, RFC 9111
+    // ---- requests that may change the resource make the cache forget it, section 4.4
+    fn through_cache(
+        &mut self,
+        request: &Request,
+        total: Option<Deadline>,
+    ) -> Result<(Response, Body), Error> {
+        if !cache::is_cacheable(request) {
+            let (response, body) = self.send_uncached(request, total)?;
+            let safe = matches!(
+                request.method.as_str(),
+                "GET" | "HEAD" | "OPTIONS" | "TRACE"
+            );
+            if !safe && response.status < 400 {
+                self.cache.lock().unwrap().invalidate(&request.url);
+            }
+            return Ok((response, body));
+        }
+
+        let stored = self.cache.lock().unwrap().lookup(request);
+        let now = SystemTime::now();
+        let asked = request.headers.cache_control();
+        if let Some(entry) = &stored {
+            let young_enough = asked
+                .max_age
+                .is_none_or(|max_age| entry.age(now).as_secs() <= max_age);
+            if entry.is_fresh(now) && !asked.no_cache && young_enough {
+                return Ok((entry.response(now), Body::from_bytes(entry.body.clone())));
+            }
+        }
+
+        // A stale response is checked on with its validators, unless the caller made the request
+        // conditional already and wants to see the 304 itself
+        let revalidating = stored.filter(|entry| {
+            entry.has_validator()
+                && !request.has_header("If-None-Match")
+                && !request.has_header("If-Modified-Since")
+        });
+        let mut conditional = request.clone();
+        if let Some(entry) = &revalidating {
+            if let Some(etag) = entry.header("ETag") {
+                conditional = conditional.header("If-None-Match", etag);
+            }
+            if let Some(last_modified) = entry.header("Last-Modified") {
+                conditional = conditional.header("If-Modified-Since", last_modified);
+            }
+        }
+        let mut request_time = SystemTime::now();
+        let (mut response, mut body) = self.send_uncached(&conditional, total)?;
+        let mut response_time = SystemTime::now();
+        if response.status == 304 && revalidating.is_some() {
+            // Nothing follows a 304, reading it hands the connection back
+            let _ = io::copy(&mut body, &mut io::sink());
+            let freshened =
+                self.cache
+                    .lock()
+                    .unwrap()
+                    .freshen(request, &response, request_time, response_time);
+            // The timings are those of asking the server
+            if let Some(entry) = freshened {
+                let mut cached = entry.response(response_time);
+                cached.request_head = response.request_head;
+                cached.timings = response.timings;
+                return Ok((cached, Body::from_bytes(entry.body)));
+            }
+            // The server validated some other version, only a full response will do, and it
+            // takes the place of the stored one
+            request_time = SystemTime::now();
+            (response, body) = self.send_uncached(request, total)?;
+            response_time = SystemTime::now();
+            if !cache::is_storable(request, &response) {
+                self.cache.lock().unwrap().invalidate(&request.url);
+            }
+        }
+
+        if !cache::is_storable(request, &response) {
+            return Ok((response, body));
+        }
+        let cache = self.cache.clone();
+        let (request, stored_response) = (request.clone(), response.clone());
+        let body = body.record(MAX_ENTRY_BYTES, move |bytes| {
+            cache.lock().unwrap().store(
+                &request,
+                &stored_response,
+                bytes,
+                request_time,
+                response_time,
+            )
+        });
+        Ok((response, body))
+    }
+
+    fn send_uncached(
+        &mut self,
+        request: &Request,
+        total: Option<Deadline>,
+    ) -> Result<(Response, Body), Error> {
+        let idle_connection = self.pool.lock().unwrap().take(&request.url);
+        if let Some(connection) = idle_connection {
+            match self.exchange(connection, request, total, Timings::since(Instant::now())) {
+                // The server may have closed the idle connection in the meantime, nothing was
+                // processed so the request is safe to send again on a fresh one
+                Err(err) if is_stale(&err) => {}
+                result => return result,
+            }
+        }
+
+        let started = Instant::now();
+        let connection = self.connect(&request.url)?;
+        let mut timings = Timings::since(started);
+        timings.connect = timings.elapsed();
+        self.exchange(connection, request, total, timings)
+    }
+
+    fn exchange(
+        &mut self,
+        mut connection: Connection,
+        request: &Request,
+        total: Option<Deadline>,
+        mut timings: Timings,
+    ) -> Result<(Response, Body), Error> {
+        // Plain http:// through an HTTP proxy is forwarded by it, anything else is tunnelled
+        let head = match self.proxy.for_url(&request.url) {
+            Some(proxy) if proxy.kind == ProxyKind::Http && request.url.scheme != "https" => {
+                request.proxy_head(proxy.authorization().as_deref().map(String::as_str))
+            }
+            _ => request.head(),
+        };
+        let first_byte = Deadline::after(self.timeouts.first_byte, "waiting for the response");
+        let stream = connection.get_mut();
+        stream
+            .socket_mut()
+            .set_deadline(Deadline::earliest(first_byte, total));
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&request.body)?;
+        stream.flush()?;
+
+        let mut response = Response::read_head(&mut connection)?;
+        timings.first_byte = timings.elapsed();
+        response.timings = timings;
+        response.request_head = head;
+        // Only the total timeout is left for the body
+        connection.get_mut().socket_mut().set_deadline(total);
+        self.cookies
+            .lock()
+            .unwrap()
+            .store(&request.url, &response.headers);
+        let body = Body::new(
+            &mut response,
+            &request.method,
+            connection,
+            (self.pool.clone(), request.url.clone()),
+            request.decode_content.then_some(self.max_decoded_size),
+        )?;
+        Ok((response, body))
+    }
+
+    fn connect(&mut self, url: &Url) -> Result<Connection, Error> {
+        let tcp_stream = match self.proxy.for_url(url) {
+            Some(proxy) => {
+                proxy.connect(url, &self.resolver, self.timeouts.connect, &self.cancel)?
+            }
+            None => stream::connect(
+                &self.resolver,
+                &url.host,
+                url.port,
+                self.timeouts.connect,
+                &self.cancel,
+            )?,
+        };
+        let socket = Socket::new(tcp_stream, self.cancel.clone());
+        if url.scheme != "https" {
+            return Ok(BufReader::new(Stream::Plain(socket)));
+        }
+
+        let config = match &self.tls_config {
+            Some(config) => config.clone(),
+            None => {
+                let config = tls::client_config(self.ca_bundle.as_deref())?;
+                self.tls_config = Some(config.clone());
+                config
+            }
+        };
+        // An IPv6 address is checked against the certificate without its brackets
+        let server_name = ServerName::try_from(url.host.trim_matches(['[', ']']).to_string())
+            .map_err(|err| Error::Tls(format!("{}, {err}", url.host)))?;
+        let connection = ClientConnection::new(config, server_name)
+            .map_err(|err| Error::Tls(err.to_string()))?;
+        Ok(BufReader::new(Stream::Tls(Box::new(StreamOwned::new(
+            connection, socket,
+        )))))
+    }
+}
+
+fn is_redirect(status: u16) -> bool {
+    matches!(status, 301 | 302 | 303 | 307 | 308)
+}
+
+fn is_stale(err: &Error) -> bool {
+    match err {
+        Error::ConnectionClosed => true,
+        Error::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::BrokenPipe
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
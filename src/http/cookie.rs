@@ -0,0 +1,454 @@
+use super::{date, HeaderMap};
+use crate::url::Url;
+use std::{
+    fs, io,
+    net::IpAddr,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+// Longest lifetime a cookie gets, the limit later revisions of RFC 6265 put in place
+const MAX_AGE_LIMIT: u64 = 400 * 24 * 60 * 60;
+
+// Cookie
+// A cookie as stored by RFC 6265 section 5.3
+// ---- "expires" is None for session cookies, which are gone once the browser quits
+// ---- "host_only" cookies were set without a Domain attribute and only go back to that exact host
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub host_only: bool,
+    pub path: String,
+    pub expires: Option<SystemTime>,
+    pub secure: bool,
+    pub http_only: bool,
+    // Order the cookie was first stored in, older cookies are sent first
+    creation: u64,
+}
+
+impl Cookie {
+    // parse
+    // Read a Set-Cookie value received from "url", RFC 6265 section 5.2 and 5.3, None when the
+    // browser has to ignore it
+    // ---- control characters are refused outright, section 5.2, they would end up in the
+    //      Cookie header sent back and tabs would break the lines of cookies.txt
+    pub fn parse(url: &Url, set_cookie: &str) -> Option<Cookie> {
+        if set_cookie.contains(|c: char| c.is_ascii_control() && c != '\t') {
+            return None;
+        }
+        let (name_value, attributes) = set_cookie.split_once(';').unwrap_or((set_cookie, ""));
+        let (name, value) = name_value.split_once('=')?;
+        let (name, value) = (name.trim(), value.trim());
+        if name.is_empty() || name.contains('\t') || value.contains('\t') {
+            return None;
+        }
+
+        let (mut expires, mut max_age, mut domain, mut path) = (None, None, None, None);
+        let (mut secure, mut http_only) = (false, false);
+        for attribute in attributes.split(';') {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "expires" => expires = date::parse(value).or(expires),
+                "max-age" => {
+                    let valid = value.starts_with(|c: char| c.is_ascii_digit() || c == '-')
+                        && value[1..].chars().all(|c| c.is_ascii_digit());
+                    if let Some(seconds) = value.parse::<i64>().ok().filter(|_| valid) {
+                        // Zero and below mean the earliest time there is, i.e. delete it now
+                        max_age = Some(match u64::try_from(seconds) {
+                            Ok(seconds) if seconds > 0 => {
+                                SystemTime::now() + Duration::from_secs(seconds.min(MAX_AGE_LIMIT))
+                            }
+                            _ => SystemTime::UNIX_EPOCH,
+                        });
+                    }
+                }
+                "domain" if !value.is_empty() && !value.contains('\t') => {
+                    domain = Some(value.trim_start_matches('.').to_ascii_lowercase());
+                }
+                "path" => {
+                    path = Some(value).filter(|path| path.starts_with('/') && !path.contains('\t'))
+                }
+                "secure" => secure = true,
+                "httponly" => http_only = true,
+                _ => {}
+            }
+        }
+
+        let host = url.host.to_ascii_lowercase();
+        let (domain, host_only) = match domain {
+            // A Domain without an inner dot would be a whole top level domain, take it as the
+            // host itself, see the public suffix note of RFC 6265 section 5.3 step 5
+            Some(domain) if domain == host => (domain, true),
+            Some(domain) if !domain.contains('.') => return None,
+            Some(domain) if domain_match(&host, &domain) => (domain, false),
+            Some(_) => return None,
+            None => (host, true),
+        };
+
+        Some(Cookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            domain,
+            host_only,
+            path: path.map_or_else(|| default_path(&url.path), String::from),
+            expires: max_age.or(expires),
+            secure,
+            http_only,
+            creation: 0,
+        })
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    // Whether the cookie goes along with a request to "url", RFC 6265 section 5.4 step 1
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = url.host.to_ascii_lowercase();
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+        domain_matches
+            && path_match(&url.path, &self.path)
+            && (!self.secure || url.scheme == "https")
+    }
+}
+
+// CookieJar
+// Every cookie the browser received, shared by all clones of a Client
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+    next_creation: u64,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        CookieJar::default()
+    }
+
+    // insert
+    // Store a cookie, replacing the one with the same name, domain and path, an expired cookie
+    // only removes the one it replaces
+    pub fn insert(&mut self, mut cookie: Cookie) {
+        let existing = self.cookies.iter().position(|old| {
+            old.name == cookie.name && old.domain == cookie.domain && old.path == cookie.path
+        });
+        cookie.creation = match existing {
+            Some(index) => self.cookies.remove(index).creation,
+            None => {
+                self.next_creation += 1;
+                self.next_creation
+            }
+        };
+        if !cookie.is_expired(SystemTime::now()) {
+            self.cookies.push(cookie);
+        }
+    }
+
+    // store
+    // Keep the cookies of every Set-Cookie header a response from "url" came with
+    pub fn store(&mut self, url: &Url, headers: &HeaderMap) {
+        for set_cookie in headers.get_all("Set-Cookie") {
+            if let Some(cookie) = Cookie::parse(url, set_cookie) {
+                self.insert(cookie);
+            }
+        }
+    }
+
+    // header
+    // Value of the Cookie header for a request to "url", cookies with longer paths come first,
+    // then the older ones, RFC 6265 section 5.4
+    pub fn header(&mut self, url: &Url) -> Option<String> {
+        let now = SystemTime::now();
+        self.cookies.retain(|cookie| !cookie.is_expired(now));
+
+        let mut matching: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.matches(url))
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        matching.sort_by(|a, b| {
+            b.path
+                .len()
+                .cmp(&a.path.len())
+                .then(a.creation.cmp(&b.creation))
+        });
+        Some(
+            matching
+                .iter()
+                .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+                .collect::<Vec<String>>()
+                .join("; "),
+        )
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie> {
+        self.cookies.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+
+    // save
+    // Write the persistent cookies to "path" in the Netscape cookies.txt format curl and wget
+    // also read, session cookies end with the session and are left out
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let now = SystemTime::now();
+        let mut file = String::from("# Netscape HTTP Cookie File\n");
+        for cookie in self.cookies.iter().filter(|cookie| !cookie.is_expired(now)) {
+            let Some(expires) = cookie.expires else {
+                continue;
+            };
+            let expires = expires
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs());
+            let flag = |value: bool| if value { "TRUE" } else { "FALSE" };
+            file.push_str(&format!(
+                "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                if cookie.http_only { "#HttpOnly_" } else { "" },
+                if cookie.host_only { "" } else { "." },
+                cookie.domain,
+                flag(!cookie.host_only),
+                cookie.path,
+                flag(cookie.secure),
+                expires,
+                cookie.name,
+                cookie.value,
+            ));
+        }
+        fs::write(path, file)
+    }
+
+    // load
+    // Add the cookies of a cookies.txt file written by save, returns how many were read
+    pub fn load(&mut self, path: &Path) -> io::Result<usize> {
+        let file = fs::read_to_string(path)?;
+        let mut loaded = 0;
+        for line in file.lines() {
+            let (http_only, line) = match line.strip_prefix("#HttpOnly_") {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [domain, subdomains, path, secure, expires, name, value] = fields[..] else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("'{line}' is not a cookies.txt line"),
+                ));
+            };
+            if line.contains(|c: char| c.is_ascii_control() && c != '\t') {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "cookies.txt line with control characters",
+                ));
+            }
+            let expires = expires
+                .parse::<u64>()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            self.insert(Cookie {
+                name: name.to_string(),
+                value: value.to_string(),
+                domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+                host_only: subdomains != "TRUE",
+                path: path.to_string(),
+                // 0 is how other tools write session cookies
+                expires: (expires > 0)
+                    .then(|| SystemTime::UNIX_EPOCH + Duration::from_secs(expires)),
+                secure: secure == "TRUE",
+                http_only,
+                creation: 0,
+            });
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+}
+
+// domain_match
+// RFC 6265 section 5.1.3, IP addresses only ever match themselves
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host[..host.len() - domain.len()].ends_with('.')
+            && host.trim_matches(['[', ']']).parse::<IpAddr>().is_err())
+}
+
+// path_match
+// RFC 6265 section 5.1.4
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+// default_path
+// Directory of the request path the cookie came from, RFC 6265 section 5.1.4
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => String::from("/"),
+        Some(index) => path[..index].to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(value: &str) -> Url {
+        Url::parse(value).unwrap()
+    }
+
+    #[test]
+    fn reads_the_attributes() {
+        let cookie = Cookie::parse(
+            &url("https://www.example.com/a/b"),
+            " id = 42 ; Path=/a; Secure; HttpOnly; Domain=.Example.com; Unknown=1",
+        )
+        .unwrap();
+        assert_eq!((cookie.name.as_str(), cookie.value.as_str()), ("id", "42"));
+        assert_eq!(cookie.domain, "example.com");
+        assert!(!cookie.host_only);
+        assert_eq!(cookie.path, "/a");
+        assert!(cookie.secure && cookie.http_only);
+        assert_eq!(cookie.expires, None);
+    }
+
+    #[test]
+    fn defaults_to_the_host_and_directory() {
+        let cookie =
+            Cookie::parse(&url("http://Example.com/docs/page"), "a=1; Path=relative").unwrap();
+        assert_eq!(cookie.domain, "example.com");
+        assert!(cookie.host_only);
+        assert_eq!(cookie.path, "/docs");
+        assert_eq!(default_path("/page"), "/");
+        assert_eq!(default_path(""), "/");
+    }
+
+    #[test]
+    fn refuses_foreign_and_top_level_domains() {
+        let page = url("http://www.example.com/");
+        assert!(Cookie::parse(&page, "a=1; Domain=other.com").is_none());
+        assert!(Cookie::parse(&page, "a=1; Domain=com").is_none());
+        assert!(Cookie::parse(&page, "a=1; Domain=ample.com").is_none());
+        assert!(Cookie::parse(&page, "=1").is_none());
+        assert!(Cookie::parse(&page, "no value").is_none());
+        // A dotless host may still name itself
+        let cookie = Cookie::parse(&url("http://localhost/"), "a=1; Domain=localhost").unwrap();
+        assert!(cookie.host_only);
+    }
+
+    #[test]
+    fn refuses_control_characters() {
+        let page = url("http://example.com/");
+        for set_cookie in [
+            "a=1\r",
+            "a=\r\nX-Injected: 1",
+            "a\0=1",
+            "a=b\tc",
+            "a\tb=c",
+            "a=1; Domain=exa\x7fmple.com",
+        ] {
+            assert!(Cookie::parse(&page, set_cookie).is_none(), "{set_cookie:?}");
+        }
+        // Whitespace around the pair is not part of it, an attribute with a tab is dropped
+        assert_eq!(Cookie::parse(&page, "a=1\t").unwrap().value, "1");
+        assert_eq!(Cookie::parse(&page, "a=1; Path=/x\ty").unwrap().path, "/");
+    }
+
+    #[test]
+    fn max_age_wins_over_expires() {
+        let page = url("http://example.com/");
+        let cookie = Cookie::parse(&page, "a=1; Expires=Wed, 09 Jun 2021 10:18:14 GMT").unwrap();
+        assert!(cookie.is_expired(SystemTime::now()));
+
+        let cookie = Cookie::parse(
+            &page,
+            "a=1; Max-Age=60; Expires=Wed, 09 Jun 2021 10:18:14 GMT",
+        )
+        .unwrap();
+        assert!(!cookie.is_expired(SystemTime::now()));
+
+        let cookie = Cookie::parse(&page, "a=1; Max-Age=-1").unwrap();
+        assert_eq!(cookie.expires, Some(SystemTime::UNIX_EPOCH));
+        // Not a number, so ignored
+        let cookie = Cookie::parse(&page, "a=1; Max-Age=+60").unwrap();
+        assert_eq!(cookie.expires, None);
+    }
+
+    #[test]
+    fn matches_domain_path_and_scheme() {
+        assert!(domain_match("www.example.com", "example.com"));
+        assert!(!domain_match("wwwexample.com", "example.com"));
+        assert!(!domain_match("1.2.3.4", "3.4"));
+        assert!(path_match("/docs/page", "/docs"));
+        assert!(path_match("/docs/page", "/docs/"));
+        assert!(!path_match("/docsets", "/docs"));
+
+        let cookie = Cookie::parse(&url("https://example.com/docs/"), "a=1; Secure").unwrap();
+        assert!(cookie.matches(&url("https://example.com/docs/page")));
+        assert!(!cookie.matches(&url("http://example.com/docs/page")));
+        assert!(!cookie.matches(&url("https://www.example.com/docs/page")));
+        assert!(!cookie.matches(&url("https://example.com/other")));
+    }
+
+    #[test]
+    fn sends_longer_paths_then_older_cookies_first() {
+        let page = url("http://example.com/a/b/c");
+        let mut jar = CookieJar::new();
+        for set_cookie in ["first=1; Path=/", "second=2; Path=/", "deep=3; Path=/a/b"] {
+            jar.insert(Cookie::parse(&page, set_cookie).unwrap());
+        }
+        // Replacing keeps the place in line
+        jar.insert(Cookie::parse(&page, "first=one; Path=/").unwrap());
+        assert_eq!(
+            jar.header(&page).as_deref(),
+            Some("deep=3; first=one; second=2")
+        );
+
+        jar.insert(Cookie::parse(&page, "second=; Max-Age=0; Path=/").unwrap());
+        assert_eq!(jar.header(&page).as_deref(), Some("deep=3; first=one"));
+        assert_eq!(jar.header(&url("http://other.com/")), None);
+    }
+
+    #[test]
+    fn saves_and_loads_persistent_cookies() {
+        let page = url("https://www.example.com/");
+        let mut jar = CookieJar::new();
+        for set_cookie in [
+            "kept=1; Max-Age=3600; Domain=example.com; Secure; HttpOnly",
+            "host=2; Max-Age=3600; Path=/x",
+            "session=3",
+        ] {
+            jar.insert(Cookie::parse(&page, set_cookie).unwrap());
+        }
+        let path = std::env::temp_dir().join(format!("cookies-{}.txt", std::process::id()));
+        jar.save(&path).unwrap();
+
+        let mut loaded = CookieJar::new();
+        assert_eq!(loaded.load(&path).unwrap(), 2);
+        let cookies: Vec<&Cookie> = loaded.iter().collect();
+        assert_eq!(cookies[0].name, "kept");
+        assert_eq!(cookies[0].domain, "example.com");
+        assert!(!cookies[0].host_only && cookies[0].secure && cookies[0].http_only);
+        assert_eq!(cookies[1].name, "host");
+        assert!(cookies[1].host_only);
+        assert_eq!(cookies[1].path, "/x");
+
+        fs::write(&path, "example.com\tFALSE\t/\tFALSE\t0\ta\tb\rc\n").unwrap();
+        assert!(loaded.load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use std::time::{Duration, SystemTime};
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+// parse
+// Parse a date the way RFC 6265 section 5.1.1 reads cookie dates, which also covers the three
+// formats HTTP allows (IMF-fixdate, RFC 850 and asctime)
+// ---- fields are picked out by shape rather than position, anything else is ignored
+pub fn parse(input: &str) -> Option<SystemTime> {
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+    let is_delimiter = |c: char| matches!(c, '\t' | ' '..='/' | ';'..='@' | '['..='`' | '{'..='~');
+    for token in input.split(is_delimiter).filter(|token| !token.is_empty()) {
+        if time.is_none() {
+            if let Some(parsed) = parse_time(token) {
+                time = Some(parsed);
+                continue;
+            }
+        }
+        if day.is_none() {
+            if let Some(parsed) = leading_digits(token, 1, 2) {
+                day = Some(parsed);
+                continue;
+            }
+        }
+        if month.is_none() {
+            let prefix = token.get(..3).unwrap_or_default().to_ascii_lowercase();
+            if let Some(index) = MONTHS.iter().position(|name| *name == prefix) {
+                month = Some(index as u32 + 1);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some(parsed) = leading_digits(token, 2, 4) {
+                year = Some(parsed);
+            }
+        }
+    }
+
+    let ((hour, minute, second), day, month, year) = (time?, day?, month?, year?);
+    let year = match year {
+        70..=99 => year + 1900,
+        0..=69 => year + 2000,
+        _ => year,
+    };
+    if year < 1601
+        || day == 0
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    let seconds = days_from_civil(year as i64, month, day) * 86400
+        + (hour * 3600 + minute * 60 + second) as i64;
+    if seconds >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64))
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(seconds.unsigned_abs()))
+    }
+}
+
+// "hh:mm:ss", each field one or two digits, whatever follows the seconds must not be a digit
+fn parse_time(token: &str) -> Option<(u32, u32, u32)> {
+    let mut fields = token.splitn(3, ':');
+    let hour = fields
+        .next()
+        .filter(|field| (1..=2).contains(&field.len()))?;
+    let minute = fields
+        .next()
+        .filter(|field| (1..=2).contains(&field.len()))?;
+    let second = leading_digits(fields.next()?, 1, 2)?;
+    Some((hour.parse().ok()?, minute.parse().ok()?, second))
+}
+
+// The number made of the first "min" to "max" digits of "token", as long as no more digits follow
+fn leading_digits(token: &str, min: usize, max: usize) -> Option<u32> {
+    let digits = token.len() - token.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if !(min..=max).contains(&digits) {
+        return None;
+    }
+    token[..digits].parse().ok()
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// days_from_civil
+// Days between 1970-01-01 and the given date in the proleptic Gregorian calendar
+// ---- algorithm by Howard Hinnant, http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(input: &str) -> Option<u64> {
+        parse(input).map(|time| {
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        })
+    }
+
+    #[test]
+    fn reads_the_http_formats() {
+        assert_eq!(seconds("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784111777));
+        assert_eq!(seconds("Sunday, 06-Nov-94 08:49:37 GMT"), Some(784111777));
+        assert_eq!(seconds("Sun Nov  6 08:49:37 1994"), Some(784111777));
+    }
+
+    #[test]
+    fn reads_what_cookies_send() {
+        assert_eq!(seconds("Thu, 01-Jan-70 00:00:00 GMT"), Some(0));
+        assert_eq!(seconds("6 november 2069 8:49:37"), Some(3150953377));
+        assert_eq!(seconds("Sat, 29 Feb 2020 23:59:59 GMT"), Some(1583020799));
+        assert!(parse("Thu, 01 Jan 1601 00:00:00 GMT").is_some());
+    }
+
+    #[test]
+    fn refuses_impossible_dates() {
+        for input in [
+            "",
+            "Sun, 06 Nov 1994",
+            "Sun, 31 Nov 1994 08:49:37 GMT",
+            "Mon, 29 Feb 2100 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:60:00 GMT",
+            "Sun, 06 Nov 1600 08:49:37 GMT",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+        ] {
+            assert_eq!(parse(input), None, "{input:?}");
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use super::Error;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use std::io::{self, prelude::*, BufReader};
+
+// Content codings advertised through Accept-Encoding
+pub const ACCEPT_ENCODING: &str = "gzip, deflate, br";
+
+// Default ceiling for a decoded body, a few KB of gzip can expand into gigabytes
+pub const MAX_DECODED_BYTES: u64 = 100 * 1024 * 1024;
+
+// decoder
+// Wrap "reader" with a decoder for every coding listed in "content_encoding", reading fails
+// once the output grows past "limit" bytes
+// ---- unknown codings are refused right away, the decoders themselves are only set up on the
+//      first read since gzip and deflate start by reading their header, which would otherwise
+//      hold up the response head until the body arrives
+pub fn decoder<'a>(
+    reader: Box<dyn Read + Send + 'a>,
+    content_encoding: &str,
+    limit: u64,
+) -> Result<Box<dyn Read + Send + 'a>, Error> {
+    if let Some(coding) = content_encoding
+        .split(',')
+        .map(str::trim)
+        .find(|coding| !is_supported(coding))
+    {
+        return Err(Error::UnsupportedEncoding(coding.to_string()));
+    }
+    Ok(Box::new(Decoded {
+        pending: Some((reader, content_encoding.to_string())),
+        inner: None,
+        failed: None,
+        limit,
+        decoded: 0,
+    }))
+}
+
+// decode
+// Decode a whole body at once
+pub fn decode(body: &[u8], content_encoding: &str, limit: u64) -> Result<Vec<u8>, Error> {
+    let mut decoded = vec![];
+    decoder(Box::new(body), content_encoding, limit)?.read_to_end(&mut decoded)?;
+    Ok(decoded)
+}
+
+fn is_supported(coding: &str) -> bool {
+    matches!(
+        coding.to_ascii_lowercase().as_str(),
+        "" | "identity" | "gzip" | "x-gzip" | "deflate" | "br"
+    )
+}
+
+// Codings are listed in the order they were applied so they get undone back to front
+fn decoding_reader<'a>(
+    reader: Box<dyn Read + Send + 'a>,
+    content_encoding: &str,
+) -> io::Result<Box<dyn Read + Send + 'a>> {
+    let mut reader = reader;
+    for coding in content_encoding.rsplit(',') {
+        reader = match coding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Box::new(GzDecoder::new(reader)),
+            "deflate" => deflate_reader(reader)?,
+            "br" => Box::new(brotli_decompressor::Decompressor::new(reader, 4096)),
+            _ => reader,
+        };
+    }
+    Ok(reader)
+}
+
+// "deflate" is meant to be zlib-wrapped, some servers send the bare deflate stream anyway
+fn deflate_reader<'a>(reader: Box<dyn Read + Send + 'a>) -> io::Result<Box<dyn Read + Send + 'a>> {
+    let mut reader = BufReader::new(reader);
+    let header = reader.fill_buf()?;
+    let zlib_wrapped = header.len() >= 2
+        && header[0] & 0x0f == 8
+        && (u16::from(header[0]) << 8 | u16::from(header[1])) % 31 == 0;
+    Ok(if zlib_wrapped {
+        Box::new(ZlibDecoder::new(reader))
+    } else {
+        Box::new(DeflateDecoder::new(reader))
+    })
+}
+
+struct Decoded<'a> {
+    // The raw body and its codings until the first read sets up "inner"
+    pending: Option<(Box<dyn Read + Send + 'a>, String)>,
+    inner: Option<Box<dyn Read + Send + 'a>>,
+    // Kind and message of the error setting up "inner" failed with
+    failed: Option<(io::ErrorKind, String)>,
+    limit: u64,
+    decoded: u64,
+}
+
+impl Read for Decoded<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some((reader, content_encoding)) = self.pending.take() {
+            match decoding_reader(reader, &content_encoding) {
+                Ok(inner) => self.inner = Some(inner),
+                Err(err) => {
+                    self.failed = Some((err.kind(), err.to_string()));
+                    return Err(err);
+                }
+            }
+        }
+        // Setting up failed on an earlier read, the error is handed out again
+        let Some(inner) = &mut self.inner else {
+            let (kind, message) = self
+                .failed
+                .as_ref()
+                .map_or((io::ErrorKind::Other, ""), |(kind, message)| {
+                    (*kind, message.as_str())
+                });
+            return Err(io::Error::new(kind, message));
+        };
+        let read = inner.read(buf).map_err(|err| match err.kind() {
+            io::ErrorKind::InvalidData
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::UnexpectedEof => io::Error::other(Error::MalformedResponse(
+                "body doesn't match its Content-Encoding",
+            )),
+            _ => err,
+        })?;
+        self.decoded += read as u64;
+        if self.decoded > self.limit {
+            return Err(io::Error::other(Error::DecodedTooLarge(self.limit)));
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{
+        write::{DeflateEncoder, GzEncoder, ZlibEncoder},
+        Compression,
+    };
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decodes_each_coding() {
+        let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+        zlib.write_all(b"hello").unwrap();
+        let mut deflate = DeflateEncoder::new(vec![], Compression::default());
+        deflate.write_all(b"hello").unwrap();
+        // "hello" as a single uncompressed brotli meta-block
+        let brotli = b"\x0b\x02\x80hello\x03";
+
+        assert_eq!(decode(&gzip(b"hello"), "gzip", 100).unwrap(), b"hello");
+        assert_eq!(decode(&gzip(b"hello"), "X-GZIP", 100).unwrap(), b"hello");
+        assert_eq!(
+            decode(&zlib.finish().unwrap(), "deflate", 100).unwrap(),
+            b"hello"
+        );
+        assert_eq!(
+            decode(&deflate.finish().unwrap(), "deflate", 100).unwrap(),
+            b"hello"
+        );
+        assert_eq!(decode(brotli, "br", 100).unwrap(), b"hello");
+        assert_eq!(decode(b"hello", "identity", 100).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn undoes_codings_back_to_front() {
+        let twice = gzip(&gzip(b"hello"));
+        assert_eq!(decode(&twice, "gzip, gzip", 100).unwrap(), b"hello");
+        assert_eq!(
+            decode(&gzip(b"hello"), "identity, gzip", 100).unwrap(),
+            b"hello"
+        );
+    }
+
+    #[test]
+    fn refuses_unknown_codings_before_reading() {
+        let err = decoder(Box::new(io::empty()), "gzip, compress", 100)
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::UnsupportedEncoding(coding) if coding == "compress"));
+    }
+
+    #[test]
+    fn stops_past_the_limit() {
+        let bomb = gzip(&vec![0; 10_000]);
+        let err = decode(&bomb, "gzip", 1_000).unwrap_err();
+        assert!(matches!(err, Error::DecodedTooLarge(1_000)));
+    }
+
+    #[test]
+    fn reports_corrupt_bodies() {
+        let mut body = gzip(b"hello world");
+        body.truncate(body.len() - 6);
+        let err = decode(&body, "gzip", 100).unwrap_err();
+        assert!(matches!(err, Error::MalformedResponse(_)), "{err}");
+    }
+
+    #[test]
+    fn reads_nothing_until_the_first_read() {
+        struct Unreadable;
+        impl Read for Unreadable {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                panic!("read before the body was asked for")
+            }
+        }
+        for coding in ["gzip", "deflate", "br"] {
+            decoder(Box::new(Unreadable), coding, 100).unwrap();
+        }
+    }
+
+    #[test]
+    fn keeps_failing_with_the_setup_error() {
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(
+                    io::ErrorKind::ConnectionReset,
+                    "reset by peer",
+                ))
+            }
+        }
+        let mut body = decoder(Box::new(Broken), "deflate", 100).unwrap();
+        for _ in 0..2 {
+            let err = body.read(&mut [0; 16]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+            assert_eq!(err.to_string(), "reset by peer");
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::url::{ParseError, Url};
+use std::{fmt, io, time::Duration};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Url(ParseError),
+    // The server's reply doesn't follow the HTTP message format
+    MalformedResponse(&'static str),
+    Tls(String),
+    // The connection was closed before any part of a response arrived
+    ConnectionClosed,
+    UnsupportedEncoding(String),
+    // The decoded body went past the size limit, most likely a decompression bomb
+    DecodedTooLarge(u64),
+    TooManyRedirects(usize),
+    // A redirect led back to a URL that was already visited
+    RedirectLoop(Box<Url>),
+    // The server answered a Range request with something other than the exact range
+    RangeIgnored,
+    SizeMismatch { expected: u64, actual: u64 },
+    // The proxy couldn't be reached or wouldn't pass the connection on
+    Proxy(String),
+    // What took too long and the limit it went past, see Timeouts
+    TimedOut(&'static str, Duration),
+    // The request was stopped through the Client's CancelToken
+    Cancelled,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{err}"),
+            Error::Url(err) => write!(f, "invalid URL, {err}"),
+            Error::MalformedResponse(reason) => write!(f, "malformed response, {reason}"),
+            Error::Tls(reason) => write!(f, "TLS error, {reason}"),
+            Error::ConnectionClosed => write!(f, "connection closed before a response was sent"),
+            Error::UnsupportedEncoding(coding) => {
+                write!(f, "content encoding '{coding}' is not supported")
+            }
+            Error::DecodedTooLarge(limit) => {
+                write!(f, "decoded body is larger than the {limit} bytes limit")
+            }
+            Error::TooManyRedirects(limit) => write!(f, "stopped after {limit} redirects"),
+            Error::RedirectLoop(url) => write!(f, "redirect loop back to {url}"),
+            Error::RangeIgnored => write!(f, "server didn't honour the Range request"),
+            Error::SizeMismatch { expected, actual } => {
+                write!(f, "expected {expected} bytes but got {actual}")
+            }
+            Error::Proxy(reason) => write!(f, "proxy error, {reason}"),
+            Error::TimedOut(what, limit) => write!(f, "{what} timed out after {limit:?}"),
+            Error::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Url(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// Errors raised while reading a body travel inside an io::Error, unwrap them back
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            return *err.into_inner().unwrap().downcast::<Error>().unwrap();
+        }
+        Error::Io(err)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Error::Url(err)
+    }
+}
@@ -0,0 +1,475 @@
+use std::slice;
+
+// HeaderMap
+// The fields of a message in the order they came in or were added, RFC 9110 section 5
+// ---- names are matched case-insensitively but kept as they were written
+// ---- a field may appear more than once, each line stays its own entry, see combined for the
+//      single value they fold into
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    fields: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        HeaderMap::default()
+    }
+
+    // Add a field after the others, keeping any that share its name
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.fields.push((name.to_string(), value.to_string()));
+    }
+
+    // Replace every field called "name" with a single one at the end
+    pub fn insert(&mut self, name: &str, value: &str) {
+        self.remove(name);
+        self.append(name, value);
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.fields
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&str, &str) -> bool) {
+        self.fields.retain(|(name, value)| keep(name, value));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    // First value of the field "name"
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Every value of the field "name", in order
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.fields
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // combined
+    // The values of the field "name" joined by commas, which is what a list field sent over
+    // several lines means, RFC 9110 section 5.3
+    // ---- Set-Cookie is the one field this doesn't work for, use get_all for it
+    pub fn combined(&self, name: &str) -> Option<String> {
+        let values: Vec<&str> = self.get_all(name).collect();
+        (!values.is_empty()).then(|| values.join(", "))
+    }
+
+    // The elements of the list field "name" over all of its lines, empty ones skipped
+    pub fn list<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.get_all(name).flat_map(split_list)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.fields.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    // Content-Type, when there is one that parses
+    pub fn media_type(&self) -> Option<MediaType> {
+        self.get("Content-Type").and_then(MediaType::parse)
+    }
+
+    pub fn content_disposition(&self) -> Option<ContentDisposition> {
+        self.get("Content-Disposition")
+            .and_then(ContentDisposition::parse)
+    }
+
+    pub fn cache_control(&self) -> CacheControl {
+        CacheControl::parse(self)
+    }
+}
+
+// The fields of a HeaderMap as (name, value), in order
+pub struct Iter<'a>(slice::Iter<'a, (String, String)>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .next()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<(String, String)> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(fields: I) -> Self {
+        HeaderMap {
+            fields: fields.into_iter().collect(),
+        }
+    }
+}
+
+// MediaType
+// A Content-Type value, RFC 9110 section 8.3.1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MediaType {
+    // type/subtype, lowercased
+    pub essence: String,
+    // Parameter names are lowercased, values unquoted
+    pub params: Vec<(String, String)>,
+}
+
+impl MediaType {
+    pub fn parse(value: &str) -> Option<MediaType> {
+        let (essence, params) = split_params(value);
+        let (kind, subtype) = essence.split_once('/')?;
+        if kind.trim().is_empty() || subtype.trim().is_empty() {
+            return None;
+        }
+        Some(MediaType {
+            essence: essence.to_ascii_lowercase(),
+            params,
+        })
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+}
+
+// ContentDisposition
+// RFC 6266, filename* (RFC 8187) wins over filename when both are there
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentDisposition {
+    // inline, attachment or some extension, lowercased
+    pub kind: String,
+    // Only the last path segment of what the server suggested, None if nothing usable is left
+    pub filename: Option<String>,
+}
+
+impl ContentDisposition {
+    pub fn parse(value: &str) -> Option<ContentDisposition> {
+        let (kind, params) = split_params(value);
+        if kind.is_empty() {
+            return None;
+        }
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let filename = param("filename*")
+            .and_then(decode_ext_value)
+            .or_else(|| param("filename").map(String::from))
+            .and_then(|filename| {
+                // The server gets to name the file, not to pick the directory it goes in
+                let filename = filename
+                    .rsplit(['/', '\\'])
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                let usable = !filename.is_empty()
+                    && filename != "."
+                    && filename != ".."
+                    && !filename.contains(char::is_control);
+                usable.then_some(filename)
+            });
+        Some(ContentDisposition {
+            kind: kind.to_ascii_lowercase(),
+            filename,
+        })
+    }
+}
+
+// CacheControl
+// The Cache-Control directives the cache acts on, RFC 9111 section 5.2
+// ---- a directive repeated with different values counts as the most restrictive one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    pub max_age: Option<u64>,
+    pub no_cache: bool,
+    pub no_store: bool,
+    pub private: bool,
+    // Each of these lets shared caches keep a response to a request with credentials, RFC 9111
+    // section 3.5
+    pub public: bool,
+    pub s_maxage: bool,
+    pub must_revalidate: bool,
+}
+
+impl CacheControl {
+    pub fn parse(headers: &HeaderMap) -> CacheControl {
+        let mut cache_control = CacheControl::default();
+        for directive in headers.list("Cache-Control") {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(unquote(argument.trim()))),
+                None => (directive.trim(), None),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                // A max-age that isn't a number makes the response stale right away
+                "max-age" => {
+                    let max_age = argument
+                        .filter(|seconds| seconds.bytes().all(|byte| byte.is_ascii_digit()))
+                        .and_then(|seconds| seconds.parse().ok())
+                        .unwrap_or(0);
+                    cache_control.max_age = Some(
+                        cache_control
+                            .max_age
+                            .map_or(max_age, |other| other.min(max_age)),
+                    );
+                }
+                // With field names the directives only cover those fields, the body is still
+                // stored and each field is sent along as usual
+                "no-cache" if argument.is_none() => cache_control.no_cache = true,
+                "no-store" => cache_control.no_store = true,
+                "private" => cache_control.private = true,
+                "public" => cache_control.public = true,
+                "s-maxage" => cache_control.s_maxage = true,
+                "must-revalidate" => cache_control.must_revalidate = true,
+                _ => {}
+            }
+        }
+        cache_control
+    }
+}
+
+// split_list
+// The elements of a comma separated list, commas inside quoted strings don't count, RFC 9110
+// section 5.6.1
+pub fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    split_outside_quotes(value, ',')
+        .into_iter()
+        .map(str::trim)
+        .filter(|element| !element.is_empty())
+}
+
+// split_params
+// A value and the ;-separated name=value parameters after it, names lowercased and values
+// unquoted, RFC 9110 section 5.6.6
+fn split_params(value: &str) -> (String, Vec<(String, String)>) {
+    let mut parts = split_outside_quotes(value, ';').into_iter();
+    let first = parts.next().unwrap_or_default().trim().to_string();
+    let params = parts
+        .filter_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            let name = name.trim();
+            (!name.is_empty()).then(|| (name.to_ascii_lowercase(), unquote(value.trim())))
+        })
+        .collect();
+    (first, params)
+}
+
+fn split_outside_quotes(value: &str, separator: char) -> Vec<&str> {
+    let (mut parts, mut start, mut quoted, mut escaped) = (vec![], 0, false, false);
+    for (index, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+// The content of a quoted-string with its escapes undone, other values as they are
+fn unquote(value: &str) -> String {
+    let Some(quoted) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    else {
+        return value.to_string();
+    };
+    let mut unquoted = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+// decode_ext_value
+// charset'language'percent-encoded, RFC 8187 section 3.2, UTF-8 and ISO-8859-1 are the
+// charsets recipients have to support
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let (charset, _language, encoded) = (parts.next()?, parts.next()?, parts.next()?);
+    let mut bytes = vec![];
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' {
+            // from_str_radix would take a sign, "%+a" is no escape
+            let hex = after
+                .get(..2)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    match charset.to_ascii_lowercase().as_str() {
+        "utf-8" => String::from_utf8(bytes).ok(),
+        "iso-8859-1" => Some(bytes.into_iter().map(char::from).collect()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_fields_in_order_and_names_as_written() {
+        let mut headers = HeaderMap::new();
+        headers.append("Set-Cookie", "a=1");
+        headers.append("Content-Type", "text/html");
+        headers.append("set-cookie", "b=2");
+        assert_eq!(headers.get("SET-COOKIE"), Some("a=1"));
+        assert_eq!(
+            headers.get_all("Set-Cookie").collect::<Vec<_>>(),
+            ["a=1", "b=2"]
+        );
+        assert_eq!(headers.combined("set-cookie").as_deref(), Some("a=1, b=2"));
+
+        headers.insert("SET-COOKIE", "c=3");
+        let fields: Vec<(&str, &str)> = headers.iter().collect();
+        assert_eq!(
+            fields,
+            [("Content-Type", "text/html"), ("SET-COOKIE", "c=3")]
+        );
+        headers.remove("content-type");
+        assert_eq!(headers.len(), 1);
+        assert!(!headers.contains("Content-Type"));
+    }
+
+    #[test]
+    fn splits_lists_outside_quotes() {
+        let mut headers = HeaderMap::new();
+        headers.append("Vary", "Accept, , Cookie");
+        headers.append("Vary", "X-\"a,b\"");
+        assert_eq!(
+            headers.list("Vary").collect::<Vec<_>>(),
+            ["Accept", "Cookie", "X-\"a,b\""]
+        );
+        assert_eq!(
+            split_list(r#"a, "b, \"c\", d", e"#).collect::<Vec<_>>(),
+            ["a", r#""b, \"c\", d""#, "e"]
+        );
+    }
+
+    #[test]
+    fn reads_media_types() {
+        let media_type = MediaType::parse(r#"Text/HTML; Charset="utf-8"; q="a;b""#).unwrap();
+        assert_eq!(media_type.essence, "text/html");
+        assert_eq!(media_type.charset(), Some("utf-8"));
+        assert_eq!(media_type.param("Q"), Some("a;b"));
+        assert!(MediaType::parse("text").is_none());
+        assert!(MediaType::parse("text/").is_none());
+        assert!(MediaType::parse("").is_none());
+    }
+
+    #[test]
+    fn reads_content_disposition() {
+        let filename =
+            |value: &str| ContentDisposition::parse(value).and_then(|parsed| parsed.filename);
+        let parsed = ContentDisposition::parse("Attachment; filename=\"report.pdf\"").unwrap();
+        assert_eq!(parsed.kind, "attachment");
+        assert_eq!(parsed.filename.as_deref(), Some("report.pdf"));
+        assert_eq!(
+            filename("attachment; filename=plain.txt; filename*=UTF-8''%E2%82%AC%20rates.txt")
+                .as_deref(),
+            Some("\u{20ac} rates.txt")
+        );
+        assert_eq!(
+            filename("attachment; filename*=iso-8859-1'en'%A3%20rates.txt").as_deref(),
+            Some("\u{a3} rates.txt")
+        );
+        // A filename* that doesn't decode leaves filename
+        assert_eq!(
+            filename("attachment; filename=fallback.txt; filename*=UTF-8''%+a").as_deref(),
+            Some("fallback.txt")
+        );
+        assert_eq!(filename("attachment; filename*=UTF-8''%4").as_deref(), None);
+        assert_eq!(filename("attachment; filename*=koi8-r''abc"), None);
+    }
+
+    #[test]
+    fn keeps_only_the_last_path_segment_of_filenames() {
+        let filename =
+            |value: &str| ContentDisposition::parse(value).and_then(|parsed| parsed.filename);
+        assert_eq!(
+            filename(r#"attachment; filename="../../etc/passwd""#).as_deref(),
+            Some("passwd")
+        );
+        assert_eq!(
+            filename(r#"attachment; filename="C:\\Windows\\evil.dll""#).as_deref(),
+            Some("evil.dll")
+        );
+        assert_eq!(
+            filename("attachment; filename*=UTF-8''..%2F..%2Fx").as_deref(),
+            Some("x")
+        );
+        assert_eq!(filename(r#"attachment; filename="dir/""#), None);
+        assert_eq!(filename(r#"attachment; filename="..""#), None);
+        assert_eq!(filename("attachment; filename*=UTF-8''a%0Ab"), None);
+        assert_eq!(filename("inline"), None);
+    }
+
+    #[test]
+    fn reads_cache_control() {
+        let mut headers = HeaderMap::new();
+        headers.append("Cache-Control", "max-age=60, no-cache=\"Set-Cookie\"");
+        headers.append("cache-control", "MAX-AGE=\"30\", Private, s-maxage=5");
+        let cache_control = headers.cache_control();
+        assert_eq!(cache_control.max_age, Some(30));
+        assert!(!cache_control.no_cache);
+        assert!(cache_control.private && cache_control.s_maxage);
+        assert!(!cache_control.public && !cache_control.must_revalidate && !cache_control.no_store);
+
+        let parsed = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.append("Cache-Control", value);
+            headers.cache_control()
+        };
+        assert_eq!(parsed("max-age=soon").max_age, Some(0));
+        assert_eq!(parsed("max-age=+60").max_age, Some(0));
+        assert!(parsed("no-cache, no-store").no_store);
+        assert_eq!(parsed(""), CacheControl::default());
+    }
+}
@@ -0,0 +1,31 @@
+pub mod auth;
+mod body;
+pub mod cache;
+mod chunked;
+mod client;
+mod cookie;
+mod date;
+pub mod encoding;
+mod error;
+pub mod header;
+mod pool;
+mod proxy;
+mod request;
+mod resolve;
+mod response;
+mod stream;
+mod timeout;
+mod tls;
+
+pub use body::Body;
+pub use cache::Cache;
+pub use chunked::ChunkedReader;
+pub use client::Client;
+pub use cookie::{Cookie, CookieJar};
+pub use error::Error;
+pub use header::HeaderMap;
+pub use proxy::{Proxy, ProxyConfig, ProxyKind};
+pub use request::Request;
+pub use resolve::{Override, Resolver};
+pub use response::{Response, Timings};
+pub use timeout::{CancelToken, Timeouts};
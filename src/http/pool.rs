@@ -0,0 +1,48 @@
+use super::stream::Stream;
+use crate::url::Url;
+use std::{collections::HashMap, fmt, io::BufReader};
+
+// Idle connections kept around per host, the rest get closed when handed back
+const MAX_IDLE_PER_HOST: usize = 4;
+
+// Connection
+// A socket to the server, buffered so whatever the server sent past a response stays with it
+pub type Connection = BufReader<Stream>;
+
+// Pool
+// Keep-alive connections waiting to be reused, keyed by scheme, host and port
+#[derive(Default)]
+pub struct Pool {
+    idle: HashMap<(String, String, u16), Vec<Connection>>,
+}
+
+impl Pool {
+    pub fn take(&mut self, url: &Url) -> Option<Connection> {
+        self.idle.get_mut(&Pool::key(url))?.pop()
+    }
+
+    pub fn put(&mut self, url: &Url, connection: Connection) {
+        let idle = self.idle.entry(Pool::key(url)).or_default();
+        if idle.len() < MAX_IDLE_PER_HOST {
+            idle.push(connection);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.idle.clear();
+    }
+
+    fn key(url: &Url) -> (String, String, u16) {
+        (url.scheme.clone(), url.host.to_ascii_lowercase(), url.port)
+    }
+}
+
+impl fmt::Debug for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.idle.iter().map(|((scheme, host, port), idle)| {
+                (format!("{scheme}://{host}:{port}"), idle.len())
+            }))
+            .finish()
+    }
+}
@@ -0,0 +1,85 @@
+use super::{encoding::ACCEPT_ENCODING, HeaderMap};
+use crate::url::Url;
+
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    // Undo the response's Content-Encoding, otherwise the body is kept as the server sent it
+    pub decode_content: bool,
+}
+
+impl Request {
+    pub fn new(method: &str, url: Url) -> Self {
+        Request {
+            method: method.to_string(),
+            url,
+            headers: HeaderMap::new(),
+            body: vec![],
+            decode_content: true,
+        }
+    }
+
+    pub fn get(url: Url) -> Self {
+        Request::new("GET", url)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    // Serialize the request line and headers, the body is sent separately
+    pub fn head(&self) -> String {
+        self.head_to(&self.url.request_target(), None)
+    }
+
+    // Same for a forward HTTP proxy, which takes the whole URL on the request line, RFC 9112
+    // section 3.2.2
+    pub(crate) fn proxy_head(&self, proxy_authorization: Option<&str>) -> String {
+        let target = format!(
+            "{}://{}{}",
+            self.url.scheme,
+            self.url.host_header(),
+            self.url.request_target()
+        );
+        self.head_to(&target, proxy_authorization)
+    }
+
+    fn head_to(&self, target: &str, proxy_authorization: Option<&str>) -> String {
+        let mut head = format!(
+            "{} {target} HTTP/1.1\r\nHost: {}\r\n",
+            self.method,
+            self.url.host_header()
+        );
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        if let Some(proxy_authorization) = proxy_authorization {
+            head.push_str(&format!("Proxy-Authorization: {proxy_authorization}\r\n"));
+        }
+        if !self.has_header("Connection") {
+            head.push_str("Connection: keep-alive\r\n");
+        }
+        if !self.has_header("Accept-Encoding") {
+            head.push_str(&format!("Accept-Encoding: {ACCEPT_ENCODING}\r\n"));
+        }
+        // Methods that define a body say so even when it's empty, RFC 9110 section 8.6
+        if !self.body.is_empty() || matches!(self.method.as_str(), "POST" | "PUT" | "PATCH") {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("\r\n");
+        head
+    }
+
+    pub fn has_header(&self, name: &str) -> bool {
+        self.headers.contains(name)
+    }
+}
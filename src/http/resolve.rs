@@ -0,0 +1,273 @@
+use crate::url;
+use std::{
+    fmt, io,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+};
+
+// Override
+// host:port pinned to a list of addresses ahead of DNS, what curl's --resolve does
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Override {
+    // Lowercased, an IPv6 literal keeps its brackets the way Url has it
+    pub host: String,
+    pub port: u16,
+    pub addresses: Vec<IpAddr>,
+}
+
+impl Override {
+    // parse
+    // "host:port:address[,address]...", IPv6 addresses may come in brackets, an IPv6 host has to
+    pub fn parse(value: &str) -> Result<Override, String> {
+        let value = value.trim();
+        let (host, rest) = match value.strip_prefix('[') {
+            Some(literal) => match literal.split_once("]:") {
+                Some((host, rest)) => (format!("[{host}]"), rest),
+                None => return Err(format!("'{value}' is not HOST:PORT:ADDRESS")),
+            },
+            None => match value.split_once(':') {
+                Some((host, rest)) => (host.to_string(), rest),
+                None => return Err(format!("'{value}' is not HOST:PORT:ADDRESS")),
+            },
+        };
+        let Some((port, addresses)) = rest.split_once(':') else {
+            return Err(format!("'{value}' is not HOST:PORT:ADDRESS"));
+        };
+        if host.is_empty() || host == "[]" {
+            return Err(format!("'{value}' has no host"));
+        }
+        let port = url::parse_port(port).map_err(|_| format!("'{port}' is not a valid port"))?;
+        let addresses = addresses
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .map(|address| {
+                address
+                    .trim_matches(['[', ']'])
+                    .parse::<IpAddr>()
+                    .map_err(|_| format!("'{address}' is not an IP address"))
+            })
+            .collect::<Result<Vec<IpAddr>, String>>()?;
+        if addresses.is_empty() {
+            return Err(format!("'{value}' has no address"));
+        }
+        Ok(Override {
+            host: host.to_ascii_lowercase(),
+            port,
+            addresses,
+        })
+    }
+}
+
+impl fmt::Display for Override {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let addresses: Vec<String> = self
+            .addresses
+            .iter()
+            .map(|address| match address {
+                IpAddr::V4(address) => address.to_string(),
+                IpAddr::V6(address) => format!("[{address}]"),
+            })
+            .collect();
+        write!(f, "{}:{}:{}", self.host, self.port, addresses.join(","))
+    }
+}
+
+// Resolver
+// Turns a host into the addresses to connect to, the overrides first and DNS otherwise
+#[derive(Clone, Debug, Default)]
+pub struct Resolver {
+    overrides: Vec<Override>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver::default()
+    }
+
+    // Pin the override's host:port, replacing what it was pinned to before
+    pub fn add(&mut self, entry: Override) {
+        self.remove(&entry.host, entry.port);
+        self.overrides.push(entry);
+    }
+
+    // Hand host:port back to DNS, false if it wasn't pinned
+    pub fn remove(&mut self, host: &str, port: u16) -> bool {
+        let count = self.overrides.len();
+        self.overrides
+            .retain(|entry| !(entry.host.eq_ignore_ascii_case(host) && entry.port == port));
+        self.overrides.len() != count
+    }
+
+    pub fn clear(&mut self) {
+        self.overrides.clear();
+    }
+
+    pub fn overrides(&self) -> &[Override] {
+        &self.overrides
+    }
+
+    // lookup
+    // Every address of "host" in the order to try them, see interleave
+    // ---- "host" is a name or an IP address, brackets around IPv6 are fine
+    pub fn lookup(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let pinned = self.overrides.iter().find(|entry| {
+            entry.port == port
+                && entry
+                    .host
+                    .trim_matches(['[', ']'])
+                    .eq_ignore_ascii_case(host.trim_matches(['[', ']']))
+        });
+        let mut addresses: Vec<SocketAddr> = match pinned {
+            Some(entry) => entry
+                .addresses
+                .iter()
+                .map(|&address| SocketAddr::new(address, port))
+                .collect(),
+            None => (host.trim_matches(['[', ']']), port)
+                .to_socket_addrs()?
+                .collect(),
+        };
+        // getaddrinfo repeats addresses once per socket type on some systems
+        let mut seen = vec![];
+        addresses.retain(|address| {
+            let new = !seen.contains(address);
+            seen.push(*address);
+            new
+        });
+        if addresses.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{host} has no address"),
+            ));
+        }
+        Ok(interleave(addresses))
+    }
+}
+
+// interleave
+// Alternate between the address families, starting with the family of the first address,
+// RFC 8305 section 4
+// ---- the system resolver already sorted them by RFC 6724 preference, which is kept within
+//      each family
+fn interleave(addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_ipv6 = addresses[0].is_ipv6();
+    let (mut first, mut second): (Vec<SocketAddr>, Vec<SocketAddr>) = addresses
+        .into_iter()
+        .partition(|address| address.is_ipv6() == first_is_ipv6);
+    let mut interleaved = Vec::with_capacity(first.len() + second.len());
+    first.reverse();
+    second.reverse();
+    while !first.is_empty() || !second.is_empty() {
+        interleaved.extend(first.pop());
+        interleaved.extend(second.pop());
+    }
+    interleaved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn addresses(list: &[&str]) -> Vec<SocketAddr> {
+        list.iter()
+            .map(|address| address.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn parses_overrides() {
+        let entry = Override::parse(" Example.com:443:127.0.0.1, [::1] ,").unwrap();
+        assert_eq!(entry.host, "example.com");
+        assert_eq!(entry.port, 443);
+        assert_eq!(
+            entry.addresses,
+            [
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(Ipv6Addr::LOCALHOST)
+            ]
+        );
+        assert_eq!(entry.to_string(), "example.com:443:127.0.0.1,[::1]");
+
+        let entry = Override::parse("[2001:DB8::1]:80:::1").unwrap();
+        assert_eq!(entry.host, "[2001:db8::1]");
+        assert_eq!(entry.addresses, [IpAddr::V6(Ipv6Addr::LOCALHOST)]);
+        assert_eq!(Override::parse(&entry.to_string()).unwrap(), entry);
+    }
+
+    #[test]
+    fn refuses_bad_overrides() {
+        for value in [
+            "example.com",
+            "example.com:443",
+            ":443:127.0.0.1",
+            "[]:443:127.0.0.1",
+            "[::1:443:127.0.0.1",
+            "example.com:+443:127.0.0.1",
+            "example.com:65536:127.0.0.1",
+            "example.com:https:127.0.0.1",
+            "example.com:443:",
+            "example.com:443:localhost",
+        ] {
+            assert!(Override::parse(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn overrides_replace_each_other_per_host_and_port() {
+        let mut resolver = Resolver::new();
+        resolver.add(Override::parse("example.com:443:10.0.0.1").unwrap());
+        resolver.add(Override::parse("example.com:80:10.0.0.2").unwrap());
+        resolver.add(Override::parse("EXAMPLE.com:443:10.0.0.3").unwrap());
+        assert_eq!(resolver.overrides().len(), 2);
+        assert_eq!(
+            resolver.lookup("Example.COM", 443).unwrap(),
+            addresses(&["10.0.0.3:443"])
+        );
+
+        assert!(resolver.remove("example.com", 80));
+        assert!(!resolver.remove("example.com", 80));
+        resolver.clear();
+        assert!(resolver.overrides().is_empty());
+    }
+
+    #[test]
+    fn looks_up_pinned_ipv6_hosts_with_or_without_brackets() {
+        let mut resolver = Resolver::new();
+        resolver.add(Override::parse("[::1]:8080:10.0.0.1,10.0.0.1,[::2]").unwrap());
+        let pinned = addresses(&["10.0.0.1:8080", "[::2]:8080"]);
+        assert_eq!(resolver.lookup("[::1]", 8080).unwrap(), pinned);
+        assert_eq!(resolver.lookup("::1", 8080).unwrap(), pinned);
+        // Other ports still go to the system resolver
+        assert_eq!(
+            resolver.lookup("::1", 80).unwrap(),
+            addresses(&["[::1]:80"])
+        );
+    }
+
+    #[test]
+    fn interleaves_address_families() {
+        let sorted = addresses(&[
+            "[::1]:80",
+            "[::2]:80",
+            "[::3]:80",
+            "10.0.0.1:80",
+            "10.0.0.2:80",
+        ]);
+        assert_eq!(
+            interleave(sorted),
+            addresses(&[
+                "[::1]:80",
+                "10.0.0.1:80",
+                "[::2]:80",
+                "10.0.0.2:80",
+                "[::3]:80"
+            ])
+        );
+        let sorted = addresses(&["10.0.0.1:80", "10.0.0.2:80", "[::1]:80"]);
+        assert_eq!(
+            interleave(sorted),
+            addresses(&["10.0.0.1:80", "[::1]:80", "10.0.0.2:80"])
+        );
+    }
+}
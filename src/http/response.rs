@@ -0,0 +1,331 @@
+use super::{Error, HeaderMap};
+use crate::url::Url;
+use std::{
+    io::prelude::*,
+    time::{Duration, Instant},
+};
+
+// Upper bound for the status line plus headers, anything bigger is most likely not HTTP
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub reason: String,
+    // Headers in the order they were received, repeated headers are kept as separate entries
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    // Fields sent after a chunked body
+    pub trailers: HeaderMap,
+    // Whether the connection can carry another request once this response is read
+    pub keep_alive: bool,
+    // Every URL the request was redirected to, the last one is where this response came from
+    pub redirects: Vec<Url>,
+    // The status line as it was received
+    pub status_line: String,
+    // Status line and headers of the request that got this response, as written to the
+    // connection, empty when none went out
+    pub request_head: String,
+    // Taken from the cache, request_head is only set when the server was asked to revalidate it
+    pub cached: bool,
+    pub timings: Timings,
+}
+
+// Timings
+// How long getting a response took, counted from when its request started out
+// ---- every step is None when it didn't happen, like connecting when an idle connection was
+//      reused or anything at all for a response out of the cache
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timings {
+    started: Option<Instant>,
+    // Until the connection was set up, through the proxy and TLS when there are any
+    pub connect: Option<Duration>,
+    // Until the status line came in
+    pub first_byte: Option<Duration>,
+    // Until the body was read to the end, see finish
+    pub total: Option<Duration>,
+}
+
+impl Timings {
+    pub(crate) fn since(started: Instant) -> Self {
+        Timings {
+            started: Some(started),
+            ..Timings::default()
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Option<Duration> {
+        self.started.map(|started| started.elapsed())
+    }
+
+    // Whoever reads the body tells when it's done
+    pub fn finish(&mut self) {
+        self.total = self.elapsed();
+    }
+}
+
+impl Response {
+    // read_head
+    // Read the status line and headers of the next response, interim responses (100 Continue
+    // and friends) that precede the real one are skipped
+    pub(crate) fn read_head<R: BufRead>(reader: &mut R) -> Result<Response, Error> {
+        loop {
+            let response = Response::read_one_head(reader)?;
+            if !(100..200).contains(&response.status) || response.status == 101 {
+                return Ok(response);
+            }
+        }
+    }
+
+    fn read_one_head<R: BufRead>(reader: &mut R) -> Result<Response, Error> {
+        let mut lines: Vec<String> = vec![];
+        let mut head_bytes = 0;
+        loop {
+            let mut line = vec![];
+            let read = reader
+                .by_ref()
+                .take((MAX_HEAD_BYTES - head_bytes) as u64 + 1)
+                .read_until(b'\n', &mut line)?;
+            head_bytes += read;
+            if read == 0 {
+                return Err(if lines.is_empty() {
+                    Error::ConnectionClosed
+                } else {
+                    Error::MalformedResponse("missing end of header section")
+                });
+            }
+            if head_bytes > MAX_HEAD_BYTES {
+                return Err(Error::MalformedResponse("header section is too large"));
+            }
+
+            let line = String::from_utf8_lossy(&line)
+                .trim_end_matches(['\r', '\n'])
+                .to_string();
+            if line.is_empty() {
+                // Tolerate stray empty lines before the status line
+                if lines.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            lines.push(line);
+        }
+        let mut lines = lines.into_iter();
+
+        // Status Line
+        let status_line = lines.next().unwrap_or_default();
+        let mut status_parts = status_line.splitn(3, ' ');
+        let version = status_parts.next().unwrap_or_default();
+        if !version.starts_with("HTTP/") {
+            return Err(Error::MalformedResponse("status line is not HTTP"));
+        }
+        let http_1_0 = version == "HTTP/1.0";
+        let status = status_parts
+            .next()
+            .filter(|code| code.len() == 3 && code.bytes().all(|byte| byte.is_ascii_digit()))
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or(Error::MalformedResponse("invalid status code"))?;
+        let reason = status_parts.next().unwrap_or_default().to_string();
+
+        // Header
+        // ---- a line starting with whitespace continues the one before it (obs-fold), RFC 9112
+        //      section 5.2
+        let mut fields: Vec<(String, String)> = vec![];
+        for line in lines {
+            if line.starts_with([' ', '\t']) {
+                let (_, value) = fields
+                    .last_mut()
+                    .ok_or(Error::MalformedResponse("folded line before any header"))?;
+                value.push(' ');
+                value.push_str(line.trim());
+                continue;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or(Error::MalformedResponse("header line without a colon"))?;
+            fields.push((name.trim().to_string(), value.trim().to_string()));
+        }
+        let headers: HeaderMap = fields.into_iter().collect();
+
+        let mut response = Response {
+            status,
+            reason,
+            headers,
+            body: vec![],
+            trailers: HeaderMap::new(),
+            keep_alive: false,
+            redirects: vec![],
+            status_line,
+            request_head: String::new(),
+            cached: false,
+            timings: Timings::default(),
+        };
+        let connection = response
+            .header("Connection")
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        response.keep_alive = if http_1_0 {
+            connection.contains("keep-alive")
+        } else {
+            !connection.contains("close")
+        };
+        Ok(response)
+    }
+
+    // Transfer-Encoding overrides Content-Length, and chunked has to be the last coding applied
+    pub(crate) fn is_chunked(&self) -> bool {
+        self.header("Transfer-Encoding").is_some_and(|codings| {
+            codings
+                .rsplit(',')
+                .next()
+                .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+        })
+    }
+
+    // content_length
+    // Content-Length is 1*DIGIT, the same value may be repeated but a body with differing ones
+    // can't be framed, RFC 9112 section 6.3
+    pub(crate) fn content_length(&self) -> Result<Option<u64>, Error> {
+        let mut content_length = None;
+        for value in self.headers.list("Content-Length") {
+            let length = value
+                .bytes()
+                .all(|byte| byte.is_ascii_digit())
+                .then(|| value.parse::<u64>().ok())
+                .flatten()
+                .ok_or(Error::MalformedResponse("invalid Content-Length"))?;
+            if content_length.is_some_and(|content_length| content_length != length) {
+                return Err(Error::MalformedResponse("conflicting Content-Length"));
+            }
+            content_length = Some(length);
+        }
+        Ok(content_length)
+    }
+
+    // First value of the header "name", header names are case-insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn head(input: &str) -> Result<Response, Error> {
+        Response::read_head(&mut input.as_bytes())
+    }
+
+    #[test]
+    fn reads_status_line_and_headers() {
+        let response = head(
+            "HTTP/1.1 404 Not Found Here\r\nContent-Type: text/html\r\nX-A:1\r\nx-a: 2\r\n\r\nbody",
+        )
+        .unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(response.reason, "Not Found Here");
+        assert_eq!(response.status_line, "HTTP/1.1 404 Not Found Here");
+        assert_eq!(response.header("content-type"), Some("text/html"));
+        assert_eq!(response.headers.combined("X-A").as_deref(), Some("1, 2"));
+        assert!(response.keep_alive);
+    }
+
+    #[test]
+    fn leaves_the_body_in_the_reader() {
+        let mut input: &[u8] = b"HTTP/1.1 200 OK\nContent-Length: 4\n\nbody";
+        Response::read_head(&mut input).unwrap();
+        assert_eq!(input, b"body");
+    }
+
+    #[test]
+    fn skips_interim_responses() {
+        let response = head("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n\r\n").unwrap();
+        assert_eq!(response.status, 200);
+        let response = head("HTTP/1.1 101 Switching Protocols\r\n\r\n").unwrap();
+        assert_eq!(response.status, 101);
+    }
+
+    #[test]
+    fn unfolds_continuation_lines() {
+        let response = head("HTTP/1.1 200 OK\r\nX-Long: one\r\n  two\r\n\tthree\r\n\r\n").unwrap();
+        assert_eq!(response.header("X-Long"), Some("one two three"));
+        assert!(head("HTTP/1.1 200 OK\r\n folded\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn works_out_keep_alive() {
+        assert!(
+            !head("HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n")
+                .unwrap()
+                .keep_alive
+        );
+        assert!(!head("HTTP/1.0 200 OK\r\n\r\n").unwrap().keep_alive);
+        assert!(
+            head("HTTP/1.0 200 OK\r\nConnection: Keep-Alive\r\n\r\n")
+                .unwrap()
+                .keep_alive
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_heads() {
+        for input in [
+            "ICY 200 OK\r\n\r\n",
+            "HTTP/1.1 +20 OK\r\n\r\n",
+            "HTTP/1.1 2000 OK\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nNo colon\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nX: 1\r\n",
+        ] {
+            assert!(
+                matches!(head(input), Err(Error::MalformedResponse(_))),
+                "{input:?}"
+            );
+        }
+        assert!(matches!(head(""), Err(Error::ConnectionClosed)));
+        let huge = format!(
+            "HTTP/1.1 200 OK\r\nX: {}\r\n\r\n",
+            "a".repeat(MAX_HEAD_BYTES)
+        );
+        assert!(matches!(head(&huge), Err(Error::MalformedResponse(_))));
+    }
+
+    #[test]
+    fn reads_content_length_strictly() {
+        let length = |value: &str| {
+            head(&format!("HTTP/1.1 200 OK\r\n{value}\r\n\r\n"))
+                .unwrap()
+                .content_length()
+        };
+        assert_eq!(length("Content-Length: 42").unwrap(), Some(42));
+        assert_eq!(length("X-None: 1").unwrap(), None);
+        assert_eq!(length("Content-Length: 7, 7").unwrap(), Some(7));
+        assert_eq!(
+            length("Content-Length: 7\r\nContent-Length: 7").unwrap(),
+            Some(7)
+        );
+        for value in [
+            "Content-Length: +5",
+            "Content-Length: -1",
+            "Content-Length: 5 5",
+            "Content-Length: 0x10",
+            "Content-Length: 5, 6",
+            "Content-Length: 5\r\nContent-Length: 6",
+        ] {
+            assert!(length(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn chunked_has_to_come_last() {
+        let chunked = |value: &str| {
+            head(&format!(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: {value}\r\n\r\n"
+            ))
+            .unwrap()
+            .is_chunked()
+        };
+        assert!(chunked("chunked"));
+        assert!(chunked("gzip, Chunked"));
+        assert!(!chunked("chunked, gzip"));
+    }
+}
@@ -0,0 +1,225 @@
+use super::{
+    timeout::{CancelToken, Deadline},
+    Error, Resolver,
+};
+use rustls::{ClientConnection, StreamOwned};
+use std::{
+    io::{self, prelude::*},
+    net::{SocketAddr, TcpStream},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+// Longest a read or write blocks before checking for cancellation and the deadline again
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// How long a connection attempt gets before the next address is tried alongside it, the delay
+// RFC 8305 section 5 recommends
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+// Longest a single connection attempt runs without a connect timeout, so its thread always ends
+const ATTEMPT_LIMIT: Duration = Duration::from_secs(60);
+
+// Stream
+// A connection to the server, either in plain text or wrapped in TLS for https://
+pub enum Stream {
+    Plain(Socket),
+    Tls(Box<StreamOwned<ClientConnection, Socket>>),
+}
+
+impl Stream {
+    pub fn socket_mut(&mut self) -> &mut Socket {
+        match self {
+            Stream::Plain(socket) => socket,
+            Stream::Tls(stream) => &mut stream.sock,
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(stream) => stream.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+// Socket
+// TCP connection that stops reading and writing at a deadline or once the request is cancelled
+// ---- the errors travel inside an io::Error, TLS sits on top and only passes them on
+pub struct Socket {
+    stream: TcpStream,
+    cancel: CancelToken,
+    deadline: Option<Deadline>,
+}
+
+impl Socket {
+    pub fn new(stream: TcpStream, cancel: CancelToken) -> Self {
+        Socket {
+            stream,
+            cancel,
+            deadline: None,
+        }
+    }
+
+    pub(crate) fn set_deadline(&mut self, deadline: Option<Deadline>) {
+        self.deadline = deadline;
+    }
+
+    // Run "operation" in slices of POLL_INTERVAL until it gets somewhere
+    fn poll<T>(
+        &mut self,
+        mut operation: impl FnMut(&mut TcpStream, Duration) -> io::Result<T>,
+    ) -> io::Result<T> {
+        loop {
+            if self.cancel.is_cancelled() {
+                return Err(io::Error::other(Error::Cancelled));
+            }
+            let mut wait = POLL_INTERVAL;
+            if let Some(deadline) = &self.deadline {
+                let left = deadline.at.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return Err(io::Error::other(deadline.error()));
+                }
+                wait = wait.min(left);
+            }
+            match operation(&mut self.stream, wait) {
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.poll(|stream, wait| {
+            stream.set_read_timeout(Some(wait))?;
+            stream.read(buf)
+        })
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.poll(|stream, wait| {
+            stream.set_write_timeout(Some(wait))?;
+            stream.write(buf)
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+// connect
+// Open a TCP connection to "host" the Happy Eyeballs way, RFC 8305: its addresses are tried in
+// turn, the next one as soon as the previous fails or ATTEMPT_DELAY goes by without an answer,
+// and the first to connect wins
+// ---- "timeout" bounds the whole race, the error names every address that was tried
+pub fn connect(
+    resolver: &Resolver,
+    host: &str,
+    port: u16,
+    timeout: Option<Duration>,
+    cancel: &CancelToken,
+) -> Result<TcpStream, Error> {
+    let addresses = resolver.lookup(host, port)?;
+    let deadline = Deadline::after(timeout, "connecting");
+    let (sender, receiver) = mpsc::channel();
+    let (mut attempts, mut failures) = (vec![], vec![]);
+    let mut next_attempt = Instant::now();
+    loop {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let now = Instant::now();
+        if let Some(deadline) = &deadline {
+            if now >= deadline.at {
+                return Err(deadline.error());
+            }
+        }
+        if attempts.len() < addresses.len() && now >= next_attempt {
+            let address = addresses[attempts.len()];
+            let limit = deadline.map_or(ATTEMPT_LIMIT, |deadline| deadline.at - now);
+            let sender = sender.clone();
+            // Attempts still running once another address won or the wait was given up end on
+            // their own within "limit", nobody is listening anymore and the stream is dropped
+            attempts.push(thread::spawn(move || {
+                let result = TcpStream::connect_timeout(&address, limit);
+                let _ = sender.send((address, result));
+            }));
+            next_attempt = now + ATTEMPT_DELAY;
+        }
+
+        let mut wait = POLL_INTERVAL;
+        if attempts.len() < addresses.len() {
+            wait = wait.min(next_attempt.saturating_duration_since(now));
+        }
+        if let Some(deadline) = &deadline {
+            wait = wait.min(deadline.at.saturating_duration_since(now));
+        }
+        match receiver.recv_timeout(wait) {
+            Ok((_, Ok(stream))) => return Ok(stream),
+            Ok((address, Err(err))) => {
+                failures.push((address, err));
+                if failures.len() == addresses.len() {
+                    for attempt in attempts {
+                        let _ = attempt.join();
+                    }
+                    return Err(match deadline {
+                        Some(deadline)
+                            if failures
+                                .iter()
+                                .all(|(_, err)| err.kind() == io::ErrorKind::TimedOut) =>
+                        {
+                            deadline.error()
+                        }
+                        _ => connect_error(host, failures),
+                    });
+                }
+                // No point waiting out the delay for an attempt that is over
+                next_attempt = Instant::now();
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+// What went wrong with each address, keeping the kind of the last error
+fn connect_error(host: &str, failures: Vec<(SocketAddr, io::Error)>) -> Error {
+    let kind = failures
+        .last()
+        .map_or(io::ErrorKind::Other, |(_, err)| err.kind());
+    let tried: Vec<String> = failures
+        .iter()
+        .map(|(address, err)| format!("{address} ({err})"))
+        .collect();
+    Error::Io(io::Error::new(
+        kind,
+        format!("couldn't connect to {host}, tried {}", tried.join(", ")),
+    ))
+}
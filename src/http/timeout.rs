@@ -0,0 +1,81 @@
+use super::Error;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+// Timeouts
+// How long each part of a request may take, None waits as long as it takes
+// ---- "connect" covers opening the TCP connection, to the proxy when there is one
+// ---- "first_byte" runs from sending the request until the status line arrives, the TLS
+//      handshake included
+// ---- "total" runs from the first request to the end of the last body, redirects included
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeouts {
+    pub connect: Option<Duration>,
+    pub first_byte: Option<Duration>,
+    pub total: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            connect: Some(Duration::from_secs(10)),
+            first_byte: Some(Duration::from_secs(30)),
+            total: None,
+        }
+    }
+}
+
+// CancelToken
+// Stops the requests in flight once cancelled, every clone of a Client shares the same one
+// ---- stays cancelled until reset, requests sent in the meantime fail right away
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+// Deadline
+// Point in time reading from a connection has to stop at, and which timeout it comes from
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Deadline {
+    pub at: Instant,
+    what: &'static str,
+    limit: Duration,
+}
+
+impl Deadline {
+    pub fn after(limit: Option<Duration>, what: &'static str) -> Option<Deadline> {
+        limit.map(|limit| Deadline {
+            at: Instant::now() + limit,
+            what,
+            limit,
+        })
+    }
+
+    pub fn earliest(a: Option<Deadline>, b: Option<Deadline>) -> Option<Deadline> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(if a.at <= b.at { a } else { b }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    pub fn error(&self) -> Error {
+        Error::TimedOut(self.what, self.limit)
+    }
+}
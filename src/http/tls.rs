@@ -0,0 +1,43 @@
+use super::Error;
+use rustls::{
+    crypto::ring,
+    pki_types::{pem::PemObject, CertificateDer},
+    ClientConfig, RootCertStore,
+};
+use std::{path::Path, sync::Arc};
+
+// client_config
+// Build a TLS configuration that trusts the system roots plus every certificate in "ca_bundle"
+// ---- system certificates that fail to load are skipped, the bundle may still cover the server
+pub fn client_config(ca_bundle: Option<&Path>) -> Result<Arc<ClientConfig>, Error> {
+    let mut roots = RootCertStore::empty();
+    roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+
+    if let Some(path) = ca_bundle {
+        let certs = CertificateDer::pem_file_iter(path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| Error::Tls(format!("can't read CA bundle {}, {err}", path.display())))?;
+        if certs.is_empty() {
+            return Err(Error::Tls(format!(
+                "no certificates found in {}",
+                path.display()
+            )));
+        }
+        for cert in certs {
+            roots.add(cert).map_err(|err| Error::Tls(err.to_string()))?;
+        }
+    }
+
+    if roots.is_empty() {
+        return Err(Error::Tls(String::from(
+            "no trusted root certificates available",
+        )));
+    }
+
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|err| Error::Tls(err.to_string()))?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
@@ -0,0 +1,15 @@
+pub mod charset;
+pub mod command;
+pub mod download;
+pub mod form;
+pub mod http;
+pub mod url;
+pub mod worker;
+
+// find_subsequence by Francis Gagné on StackOverflow
+// Find the starting index of the byte subset "needle" in "haystack"
+pub fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
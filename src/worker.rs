@@ -0,0 +1,155 @@
+use crate::{
+    download::{self, Progress},
+    http::{Body, Client, Error, Request, Response},
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{prelude::*, BufWriter},
+    path::PathBuf,
+    sync::mpsc,
+    thread,
+};
+
+// Job
+// Network work handed to the worker thread, each job brings the Client to use so settings
+// changed in the meantime apply from the next job on
+pub enum Job {
+    // Send "request" and read its body into the response, unless "stream_body" hands a 2XX
+    // body back to be streamed by a Download job
+    Fetch {
+        client: Client,
+        request: Request,
+        stream_body: bool,
+    },
+    // Write the body of a fetched response to "path", over "segments" connections when there
+    // is more than one
+    // ---- "resumed_from" bytes are already on disk, the body gets appended to them
+    Download {
+        client: Client,
+        request: Request,
+        response: Box<Response>,
+        body: Body,
+        path: PathBuf,
+        segments: usize,
+        total: Option<u64>,
+        resumed_from: u64,
+    },
+}
+
+// Event
+// What the worker sends back, in the order it happens
+pub enum Event {
+    // The request of a Fetch and its answer, with the body still to be read for a streamed one
+    Fetched(Box<Request>, Result<(Box<Response>, Body), Error>),
+    // A Download moving along, as often as the download reports it
+    Progress(Progress),
+    // A Download done, with the number of connections it came down over
+    Downloaded(Result<usize, Error>),
+}
+
+// Worker
+// A thread doing the requests one job at a time so the thread owning the screen never waits on
+// the network
+// ---- the thread ends once the Worker is dropped and the job at hand is done, cancel the
+//      Client of that job to cut it short
+pub struct Worker {
+    jobs: mpsc::Sender<Job>,
+    events: mpsc::Receiver<Event>,
+}
+
+impl Worker {
+    pub fn spawn() -> Worker {
+        let (jobs, job_rx) = mpsc::channel::<Job>();
+        let (event_tx, events) = mpsc::channel::<Event>();
+        thread::spawn(move || {
+            for job in job_rx {
+                // Nobody is listening anymore once the Worker is gone
+                if !run(job, &event_tx) {
+                    break;
+                }
+            }
+        });
+        Worker { jobs, events }
+    }
+
+    pub fn send(&self, job: Job) {
+        // The thread only ends early if a job panicked, which would have brought down the
+        // browser with it anyway
+        self.jobs.send(job).unwrap();
+    }
+
+    // The next event if there is one already, never blocks
+    pub fn try_recv(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+}
+
+// run
+// Do "job", telling "events" how it goes, false once they aren't received anymore
+fn run(job: Job, events: &mpsc::Sender<Event>) -> bool {
+    match job {
+        Job::Fetch {
+            mut client,
+            request,
+            stream_body,
+        } => {
+            let fetched = client
+                .send_streaming(&request)
+                .and_then(|(mut response, mut body)| {
+                    if !stream_body || !(200..300).contains(&response.status) {
+                        body.read_to_end(&mut response.body)?;
+                        response.timings.finish();
+                    }
+                    Ok((Box::new(response), body))
+                });
+            events
+                .send(Event::Fetched(Box::new(request), fetched))
+                .is_ok()
+        }
+        Job::Download {
+            client,
+            request,
+            response,
+            mut body,
+            path,
+            segments,
+            total,
+            resumed_from,
+        } => {
+            // Progress gets dropped rather than failing the download if the receiving end is
+            // gone, the result tells whether it still is
+            let on_progress = |progress: &Progress| {
+                let _ = events.send(Event::Progress(*progress));
+            };
+            let downloaded = if segments > 1 {
+                download::stream_segmented(
+                    &client,
+                    &request,
+                    &response,
+                    body,
+                    &path,
+                    segments,
+                    on_progress,
+                )
+            } else {
+                let file = if resumed_from > 0 {
+                    OpenOptions::new().append(true).open(&path)
+                } else {
+                    File::create(&path)
+                };
+                file.map_err(Error::from)
+                    .and_then(|f| {
+                        download::stream_to(
+                            &mut body,
+                            &mut BufWriter::new(f),
+                            total,
+                            resumed_from,
+                            on_progress,
+                        )
+                    })
+                    .map(|_| 1)
+            };
+            events.send(Event::Downloaded(downloaded)).is_ok()
+        }
+    }
+}
@@ -0,0 +1,277 @@
+mod support;
+
+use flate2::{write::GzEncoder, Compression};
+use rust_simple_web_browser::{
+    http::{Client, Error, Request, Response},
+    url::Url,
+};
+use std::{
+    io::{prelude::*, BufReader},
+    net::TcpListener,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+use support::{response, Server};
+
+fn get(client: &mut Client, url: &str) -> Response {
+    client
+        .send(&Request::get(Url::parse(url).unwrap()))
+        .unwrap()
+}
+
+#[test]
+fn get_reads_status_headers_and_body() {
+    let server = Server::start(|_| {
+        response(
+            "200 OK",
+            &[("Content-Type", "text/plain"), ("X-Test", "1")],
+            b"hello",
+        )
+    });
+    let response = get(&mut Client::new(), &server.url("/page?q=1"));
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.reason, "OK");
+    assert_eq!(response.header("x-test"), Some("1"));
+    assert_eq!(response.body, b"hello");
+
+    let received = server.received();
+    assert_eq!(received[0].request_line(), "GET /page?q=1 HTTP/1.1");
+    assert_eq!(
+        received[0].header("Host"),
+        Some(format!("127.0.0.1:{}", server.port).as_str())
+    );
+}
+
+#[test]
+fn post_sends_its_body() {
+    let server = Server::start(|request| response("201 Created", &[], &request.body));
+    let request = Request::new("POST", Url::parse(&server.url("/items")).unwrap())
+        .header("Content-Type", "application/json")
+        .body(br#"{"a":1}"#.to_vec());
+    let response = Client::new().send(&request).unwrap();
+
+    assert_eq!(response.status, 201);
+    assert_eq!(response.body, br#"{"a":1}"#);
+    let received = server.received();
+    assert_eq!(received[0].header("Content-Length"), Some("7"));
+    assert_eq!(received[0].header("Content-Type"), Some("application/json"));
+}
+
+#[test]
+fn error_statuses_are_responses() {
+    let server = Server::start(|_| response("404 Not Found", &[], b"missing"));
+    let response = get(&mut Client::new(), &server.url("/"));
+    assert_eq!(response.status, 404);
+    assert_eq!(response.body, b"missing");
+}
+
+#[test]
+fn reuses_the_connection() {
+    let server = Server::start(|_| response("200 OK", &[("Cache-Control", "no-store")], b"same"));
+    let mut client = Client::new();
+    for _ in 0..3 {
+        assert_eq!(get(&mut client, &server.url("/")).body, b"same");
+    }
+    assert_eq!(server.received().len(), 3);
+    assert_eq!(server.connections(), 1);
+}
+
+#[test]
+fn connection_close_opens_a_new_connection() {
+    let server = Server::start(|_| response("200 OK", &[("Connection", "close")], b"bye"));
+    let mut client = Client::new();
+    for _ in 0..2 {
+        assert_eq!(get(&mut client, &server.url("/")).body, b"bye");
+    }
+    assert_eq!(server.connections(), 2);
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn reuses_the_connection_after_a_compressed_body() {
+    let server = Server::start(|_| {
+        response(
+            "200 OK",
+            &[("Content-Encoding", "gzip"), ("Cache-Control", "no-store")],
+            &gzip(b"squeezed"),
+        )
+    });
+    let mut client = Client::new();
+    for _ in 0..3 {
+        assert_eq!(get(&mut client, &server.url("/")).body, b"squeezed");
+    }
+    assert_eq!(server.connections(), 1);
+}
+
+#[test]
+fn keeps_the_trailers_of_a_compressed_chunked_body() {
+    let server = Server::start(|_| {
+        let body = gzip(b"chunked and squeezed");
+        let (first, second) = body.split_at(10);
+        let mut response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\
+            Content-Encoding: gzip\r\nCache-Control: no-store\r\n\r\n"
+            .to_vec();
+        for chunk in [first, second] {
+            response.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            response.extend_from_slice(chunk);
+            response.extend_from_slice(b"\r\n");
+        }
+        response.extend_from_slice(b"0\r\nX-Checksum: abc\r\n\r\n");
+        response
+    });
+    let mut client = Client::new();
+    for _ in 0..2 {
+        let response = get(&mut client, &server.url("/"));
+        assert_eq!(response.body, b"chunked and squeezed");
+        assert_eq!(response.trailers.get("X-Checksum"), Some("abc"));
+    }
+    assert_eq!(server.connections(), 1);
+}
+
+#[test]
+fn hands_the_head_over_before_a_compressed_body_arrives() {
+    for coding in ["gzip", "deflate", "br"] {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            support::read_request(&mut BufReader::new(stream.try_clone().unwrap())).unwrap();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Encoding: {coding}\r\nContent-Length: 100\r\n\r\n"
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            // The body never comes
+            thread::sleep(Duration::from_secs(2));
+        });
+
+        let started = Instant::now();
+        let url = Url::parse(&format!("http://127.0.0.1:{port}/")).unwrap();
+        let (response, _body) = Client::new().send_streaming(&Request::get(url)).unwrap();
+        assert_eq!(response.status, 200);
+        assert!(started.elapsed() < Duration::from_secs(1), "{coding}");
+    }
+}
+
+#[test]
+fn stores_the_new_version_a_304_points_to() {
+    let plain_requests = AtomicUsize::new(0);
+    let server = Server::start(move |request| {
+        if request.header("If-None-Match").is_some() {
+            return response("304 Not Modified", &[("ETag", "\"v2\"")], b"");
+        }
+        match plain_requests.fetch_add(1, Ordering::SeqCst) {
+            0 => response(
+                "200 OK",
+                &[("ETag", "\"v1\""), ("Cache-Control", "no-cache")],
+                b"one",
+            ),
+            _ => response(
+                "200 OK",
+                &[("ETag", "\"v2\""), ("Cache-Control", "no-cache")],
+                b"two",
+            ),
+        }
+    });
+    let mut client = Client::new();
+    assert_eq!(get(&mut client, &server.url("/")).body, b"one");
+    // Revalidating "v1" gets a 304 for "v2", which has to be fetched in full
+    assert_eq!(get(&mut client, &server.url("/")).body, b"two");
+    let response = get(&mut client, &server.url("/"));
+    assert_eq!(response.body, b"two");
+    assert!(response.cached);
+
+    let received = server.received();
+    let validators: Vec<Option<&str>> = received
+        .iter()
+        .map(|request| request.header("If-None-Match"))
+        .collect();
+    assert_eq!(validators, [None, Some("\"v1\""), None, Some("\"v2\"")]);
+}
+
+#[test]
+fn redirects_keep_credentials_within_the_origin() {
+    let other = Server::start(|_| response("200 OK", &[], b"elsewhere"));
+    let elsewhere = other.url("/landing");
+    let server = Server::start(move |request| match request.request_line() {
+        "GET /start HTTP/1.1" => response("302 Found", &[("Location", "/next")], b""),
+        _ => response("302 Found", &[("Location", &elsewhere)], b""),
+    });
+    let request = Request::get(Url::parse(&server.url("/start")).unwrap())
+        .header("Authorization", "Basic dXNlcjpwYXNz")
+        .header("Proxy-Authorization", "Basic cHJveHk6cGFzcw==")
+        .header("Cookie", "session=1");
+    let response = Client::new().send(&request).unwrap();
+    assert_eq!(response.body, b"elsewhere");
+
+    let same_origin = &server.received()[1];
+    assert_eq!(same_origin.request_line(), "GET /next HTTP/1.1");
+    assert_eq!(
+        same_origin.header("Authorization"),
+        Some("Basic dXNlcjpwYXNz")
+    );
+    assert_eq!(same_origin.header("Cookie"), Some("session=1"));
+    let cross_origin = &other.received()[0];
+    for name in ["Authorization", "Proxy-Authorization", "Cookie"] {
+        assert_eq!(cross_origin.header(name), None, "{name}");
+    }
+}
+
+// A server that takes the connection and the request but never answers
+fn silent_server() -> (String, thread::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://127.0.0.1:{}/",
+        listener.local_addr().unwrap().port()
+    );
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        // Reads until the client hangs up
+        let _ = std::io::copy(&mut stream, &mut std::io::sink());
+    });
+    (url, server)
+}
+
+#[test]
+fn gives_up_waiting_for_the_first_byte() {
+    let (url, server) = silent_server();
+    let mut client = Client::new();
+    client.timeouts.first_byte = Some(Duration::from_millis(200));
+
+    let started = Instant::now();
+    let err = client
+        .send(&Request::get(Url::parse(&url).unwrap()))
+        .unwrap_err();
+    assert!(
+        matches!(err, Error::TimedOut(_, limit) if limit == Duration::from_millis(200)),
+        "{err}"
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+    drop(client);
+    server.join().unwrap();
+}
+
+#[test]
+fn cancelling_stops_a_read() {
+    let (url, server) = silent_server();
+    let mut client = Client::new();
+    client.timeouts.first_byte = None;
+    let cancel = client.cancel.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        cancel.cancel();
+    });
+
+    let err = client
+        .send(&Request::get(Url::parse(&url).unwrap()))
+        .unwrap_err();
+    assert!(matches!(err, Error::Cancelled), "{err}");
+    drop(client);
+    server.join().unwrap();
+}
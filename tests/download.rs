@@ -0,0 +1,114 @@
+mod support;
+
+use rust_simple_web_browser::{
+    download,
+    http::{Client, Request},
+    url::Url,
+};
+use std::{fs, path::PathBuf};
+use support::{response, Received, Server};
+
+const SIZE: usize = 3 * 1024 * 1024;
+
+fn file() -> Vec<u8> {
+    (0..SIZE).map(|index| (index % 251) as u8).collect()
+}
+
+// The file in full, or the byte range the request asks for when "ranges" is on
+fn serve(request: &Received, ranges: bool) -> Vec<u8> {
+    let file = file();
+    let headers = [("Accept-Ranges", "bytes"), ("ETag", "\"v1\"")];
+    let range = request
+        .header("Range")
+        .filter(|_| ranges)
+        .and_then(|range| range.strip_prefix("bytes="))
+        .and_then(|range| range.split_once('-'));
+    match range {
+        Some((start, end)) => {
+            let (start, end): (usize, usize) = (start.parse().unwrap(), end.parse().unwrap());
+            let content_range = format!("bytes {start}-{end}/{SIZE}");
+            let headers = [headers[0], headers[1], ("Content-Range", &content_range)];
+            response("206 Partial Content", &headers, &file[start..=end])
+        }
+        None => response("200 OK", &headers, &file),
+    }
+}
+
+fn target(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{name}-{}", std::process::id()))
+}
+
+#[test]
+fn fetches_every_segment_by_range() {
+    let server = Server::start(|request| serve(request, true));
+    let client = Client::new();
+    let request = Request::get(Url::parse(&server.url("/file.bin")).unwrap());
+    let (response, body) = client.clone().send_streaming(&request).unwrap();
+    let path = target("segmented");
+
+    let connections =
+        download::stream_segmented(&client, &request, &response, body, &path, 3, |_| {}).unwrap();
+
+    assert_eq!(connections, 3);
+    assert_eq!(fs::read(&path).unwrap(), file());
+    let ranges: Vec<String> = server
+        .received()
+        .iter()
+        .filter_map(|request| request.header("Range").map(String::from))
+        .collect();
+    assert_eq!(
+        ranges,
+        [
+            "bytes=0-1048575",
+            "bytes=1048576-2097151",
+            "bytes=2097152-3145727"
+        ]
+    );
+    assert!(server
+        .received()
+        .iter()
+        .skip(1)
+        .all(|request| request.header("If-Range") == Some("\"v1\"")));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn streams_the_whole_file_when_ranges_are_ignored() {
+    let server = Server::start(|request| serve(request, false));
+    let client = Client::new();
+    let request = Request::get(Url::parse(&server.url("/file.bin")).unwrap());
+    let (response, body) = client.clone().send_streaming(&request).unwrap();
+    let path = target("unsegmented");
+
+    let connections =
+        download::stream_segmented(&client, &request, &response, body, &path, 3, |_| {}).unwrap();
+
+    assert_eq!(connections, 1);
+    assert_eq!(fs::read(&path).unwrap(), file());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn clamps_the_segment_count() {
+    let server = Server::start(|request| serve(request, true));
+    let client = Client::new();
+    let request = Request::get(Url::parse(&server.url("/file.bin")).unwrap());
+    let path = target("clamped");
+
+    for (requested, expected) in [(0, 1), (64, 3)] {
+        let (response, body) = client.clone().send_streaming(&request).unwrap();
+        let connections = download::stream_segmented(
+            &client,
+            &request,
+            &response,
+            body,
+            &path,
+            requested,
+            |_| {},
+        )
+        .unwrap();
+        assert_eq!(connections, expected);
+        assert_eq!(fs::read(&path).unwrap(), file());
+    }
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,253 @@
+// Loopback stand-ins for the servers and proxies the client talks to, shared by the integration
+// tests
+#![allow(dead_code)]
+
+use std::{
+    io::{prelude::*, BufReader},
+    net::{Ipv4Addr, Ipv6Addr, Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+// Received
+// A request as it arrived, the head exactly as sent and the body read by its Content-Length
+#[derive(Clone, Debug)]
+pub struct Received {
+    pub head: String,
+    pub body: Vec<u8>,
+}
+
+impl Received {
+    pub fn request_line(&self) -> &str {
+        self.head.lines().next().unwrap_or_default()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+}
+
+// Server
+// HTTP/1.1 server on 127.0.0.1 answering every request on a connection with whatever
+// "respond" returns, until the client or a "Connection: close" answer ends it
+pub struct Server {
+    pub port: u16,
+    connections: Arc<AtomicUsize>,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl Server {
+    pub fn start(respond: impl Fn(&Received) -> Vec<u8> + Send + Sync + 'static) -> Server {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(Mutex::new(vec![]));
+        let respond = Arc::new(respond);
+        {
+            let (connections, received) = (connections.clone(), received.clone());
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(stream) = stream else { break };
+                    connections.fetch_add(1, Ordering::SeqCst);
+                    let (received, respond) = (received.clone(), respond.clone());
+                    thread::spawn(move || serve(stream, &received, &*respond));
+                }
+            });
+        }
+        Server {
+            port,
+            connections,
+            received,
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{path}", self.port)
+    }
+
+    // Connections accepted so far
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+// Socks5Connect
+// What a client asked a Socks5 stand-in for, the target is a name or an IP address as sent
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Socks5Connect {
+    pub credentials: Option<(String, String)>,
+    pub target: String,
+    pub port: u16,
+}
+
+// Socks5
+// SOCKS5 proxy on 127.0.0.1 that wants "credentials" when given, and then plays the target
+// itself, answering requests like a Server does
+pub struct Socks5 {
+    pub port: u16,
+    connects: Arc<Mutex<Vec<Socks5Connect>>>,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl Socks5 {
+    pub fn start(
+        credentials: Option<(&str, &str)>,
+        respond: impl Fn(&Received) -> Vec<u8> + Send + Sync + 'static,
+    ) -> Socks5 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let credentials = credentials.map(|(user, pass)| (user.to_string(), pass.to_string()));
+        let connects = Arc::new(Mutex::new(vec![]));
+        let received = Arc::new(Mutex::new(vec![]));
+        let respond = Arc::new(respond);
+        {
+            let (connects, received) = (connects.clone(), received.clone());
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else { break };
+                    let (connects, received, respond) =
+                        (connects.clone(), received.clone(), respond.clone());
+                    let credentials = credentials.clone();
+                    thread::spawn(move || {
+                        if let Some(connect) = socks5_handshake(&mut stream, &credentials) {
+                            connects.lock().unwrap().push(connect);
+                            serve(stream, &received, &*respond);
+                        }
+                    });
+                }
+            });
+        }
+        Socks5 {
+            port,
+            connects,
+            received,
+        }
+    }
+
+    pub fn connects(&self) -> Vec<Socks5Connect> {
+        self.connects.lock().unwrap().clone()
+    }
+
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+// RFC 1928 and RFC 1929 from the proxy's side, None when the client was turned away
+fn socks5_handshake(
+    stream: &mut TcpStream,
+    credentials: &Option<(String, String)>,
+) -> Option<Socks5Connect> {
+    let greeting = read_bytes(stream, 2)?;
+    let methods = read_bytes(stream, greeting[1] as usize)?;
+    let wanted = if credentials.is_some() { 0x02 } else { 0x00 };
+    if greeting[0] != 0x05 || !methods.contains(&wanted) {
+        stream.write_all(&[0x05, 0xFF]).ok()?;
+        return None;
+    }
+    stream.write_all(&[0x05, wanted]).ok()?;
+
+    let mut sent = None;
+    if credentials.is_some() {
+        let length = read_bytes(stream, 2)?[1] as usize;
+        let username = String::from_utf8(read_bytes(stream, length)?).ok()?;
+        let length = read_bytes(stream, 1)?[0] as usize;
+        let password = String::from_utf8(read_bytes(stream, length)?).ok()?;
+        sent = Some((username, password));
+        let status = if &sent == credentials { 0x00 } else { 0x01 };
+        stream.write_all(&[0x01, status]).ok()?;
+        if status != 0x00 {
+            return None;
+        }
+    }
+
+    let request = read_bytes(stream, 4)?;
+    let target = match request[3] {
+        0x01 => Ipv4Addr::from(<[u8; 4]>::try_from(read_bytes(stream, 4)?).ok()?).to_string(),
+        0x04 => Ipv6Addr::from(<[u8; 16]>::try_from(read_bytes(stream, 16)?).ok()?).to_string(),
+        0x03 => {
+            let length = read_bytes(stream, 1)?[0] as usize;
+            String::from_utf8(read_bytes(stream, length)?).ok()?
+        }
+        _ => return None,
+    };
+    let port = read_bytes(stream, 2)?;
+    stream
+        .write_all(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0, 0])
+        .ok()?;
+    Some(Socks5Connect {
+        credentials: sent,
+        target,
+        port: u16::from_be_bytes([port[0], port[1]]),
+    })
+}
+
+fn read_bytes(stream: &mut TcpStream, length: usize) -> Option<Vec<u8>> {
+    let mut bytes = vec![0; length];
+    stream.read_exact(&mut bytes).ok().map(|_| bytes)
+}
+
+fn serve(
+    stream: TcpStream,
+    received: &Mutex<Vec<Received>>,
+    respond: &dyn Fn(&Received) -> Vec<u8>,
+) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+    while let Some(request) = read_request(&mut reader) {
+        received.lock().unwrap().push(request.clone());
+        let response = respond(&request);
+        if stream.write_all(&response).is_err() {
+            return;
+        }
+        // HTTP/1.0 clients read the body up to the end of the connection
+        let head = String::from_utf8_lossy(&response).to_ascii_lowercase();
+        if head.contains("\r\nconnection: close\r\n")
+            || request.request_line().ends_with("HTTP/1.0")
+        {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+    }
+}
+
+pub fn read_request(reader: &mut impl BufRead) -> Option<Received> {
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        head.push_str(&line);
+        if line == "\r\n" {
+            break;
+        }
+    }
+    let mut request = Received { head, body: vec![] };
+    let length = request
+        .header("Content-Length")
+        .map_or(0, |length| length.parse().unwrap());
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).ok()?;
+    Some(request)
+}
+
+// A complete response with Content-Length set from "body"
+pub fn response(status: &str, headers: &[(&str, &str)], body: &[u8]) -> Vec<u8> {
+    let mut head = format!("HTTP/1.1 {status}\r\nContent-Length: {}\r\n", body.len());
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    [head.as_bytes(), body].concat()
+}